@@ -0,0 +1,279 @@
+//! References to units in A1 notation, such as `B12`, `AA3`, `$A$1` and `A1:C9`.
+//!
+//! Columns are named `A` to `Z`, then `AA` to `ZZ`, and so on. Rows are numbered from 1. Both
+//! convert to and from zero-indexed positions represented in `(col, row)`.
+
+use std::{error, fmt, ops::RangeInclusive, str::FromStr};
+
+/// A reference to a unit. The `$` of an absolute column or row is kept for formatting, but
+/// has no other effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellRef {
+    pub col: usize,
+    pub row: usize,
+    pub col_absolute: bool,
+    pub row_absolute: bool,
+}
+
+impl CellRef {
+    pub fn new(col: usize, row: usize) -> Self {
+        Self {
+            col,
+            row,
+            col_absolute: false,
+            row_absolute: false,
+        }
+    }
+
+    /// Gets the position. Zero-indexed. Represented in `(col, row)`.
+    pub fn pos(&self) -> (usize, usize) {
+        (self.col, self.row)
+    }
+
+    /// Gets the name of the column of `index`, e.g. `AA` for 26.
+    pub fn col_name(index: usize) -> String {
+        // Counted from the last letter, so that no index overflows.
+        let mut name = vec![];
+        let mut n = index;
+        loop {
+            name.push(b'A' + (n % 26) as u8);
+            if n < 26 {
+                break;
+            }
+            n = n / 26 - 1;
+        }
+        name.reverse();
+
+        String::from_utf8(name).unwrap()
+    }
+
+    /// Parses the name of a column, case-insensitively. Returns its index.
+    pub fn parse_col(name: &str) -> Option<usize> {
+        if name.is_empty() {
+            return None;
+        }
+
+        // Counted one past the index, in a wider type so that the last index fits.
+        let mut n: u128 = 0;
+        for c in name.chars() {
+            if !c.is_ascii_alphabetic() {
+                return None;
+            }
+            let digit = (c.to_ascii_uppercase() as u8 - b'A' + 1) as u128;
+            n = n.checked_mul(26)?.checked_add(digit)?;
+        }
+
+        usize::try_from(n - 1).ok()
+    }
+}
+
+impl FromStr for CellRef {
+    type Err = ParseRefError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseRefError(s.to_owned());
+
+        let (col_absolute, rest) = match s.strip_prefix('$') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let split = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .ok_or_else(err)?;
+        let (letters, rest) = rest.split_at(split);
+        let (row_absolute, digits) = match rest.strip_prefix('$') {
+            Some(digits) => (true, digits),
+            None => (false, rest),
+        };
+
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+        let col = Self::parse_col(letters).ok_or_else(err)?;
+        let row = digits
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .ok_or_else(err)?;
+
+        Ok(Self {
+            col,
+            row,
+            col_absolute,
+            row_absolute,
+        })
+    }
+}
+
+impl fmt::Display for CellRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}",
+            if self.col_absolute { "$" } else { "" },
+            Self::col_name(self.col),
+            if self.row_absolute { "$" } else { "" },
+            self.row as u128 + 1
+        )
+    }
+}
+
+impl From<(usize, usize)> for CellRef {
+    fn from(pos: (usize, usize)) -> Self {
+        Self::new(pos.0, pos.1)
+    }
+}
+
+impl From<CellRef> for (usize, usize) {
+    fn from(cell: CellRef) -> Self {
+        cell.pos()
+    }
+}
+
+/// A rectangular range of units, both corners inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellRange {
+    pub start: CellRef,
+    pub end: CellRef,
+}
+
+impl CellRange {
+    pub fn new(start: CellRef, end: CellRef) -> Self {
+        Self { start, end }
+    }
+
+    /// Gets the upper left corner, wherever the range starts.
+    pub fn min(&self) -> (usize, usize) {
+        (
+            self.start.col.min(self.end.col),
+            self.start.row.min(self.end.row),
+        )
+    }
+
+    /// Gets the lower right corner, wherever the range ends.
+    pub fn max(&self) -> (usize, usize) {
+        (
+            self.start.col.max(self.end.col),
+            self.start.row.max(self.end.row),
+        )
+    }
+
+    /// Checks if `pos` lies in the range.
+    pub fn contains(&self, pos: (usize, usize)) -> bool {
+        let (min, max) = (self.min(), self.max());
+        (min.0..=max.0).contains(&pos.0) && (min.1..=max.1).contains(&pos.1)
+    }
+}
+
+/// Parses `A1:C9`, or a single reference as a range of one unit.
+impl FromStr for CellRange {
+    type Err = ParseRefError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((start, end)) => Ok(Self::new(start.parse()?, end.parse()?)),
+            None => {
+                let cell = s.parse()?;
+                Ok(Self::new(cell, cell))
+            }
+        }
+    }
+}
+
+impl fmt::Display for CellRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.start, self.end)
+    }
+}
+
+impl From<CellRange> for RangeInclusive<(usize, usize)> {
+    fn from(range: CellRange) -> Self {
+        range.min()..=range.max()
+    }
+}
+
+/// Error when parsing a reference. Contains what failed to be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRefError(String);
+
+impl fmt::Display for ParseRefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid reference `{}`", self.0)
+    }
+}
+
+impl error::Error for ParseRefError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn col_names() {
+        for (index, name) in [(0, "A"), (25, "Z"), (26, "AA"), (701, "ZZ"), (702, "AAA")] {
+            assert_eq!(CellRef::col_name(index), name);
+            assert_eq!(CellRef::parse_col(name), Some(index));
+        }
+        assert_eq!(CellRef::parse_col("ab"), Some(27));
+
+        let name = CellRef::col_name(usize::MAX);
+        assert_eq!(CellRef::parse_col(&name), Some(usize::MAX));
+        assert_eq!(CellRef::parse_col(&format!("{}A", name)), None);
+        assert_eq!(CellRef::parse_col(""), None);
+        assert_eq!(CellRef::parse_col("A1"), None);
+    }
+
+    #[test]
+    fn parse_refs() {
+        assert_eq!("B12".parse(), Ok(CellRef::new(1, 11)));
+        assert_eq!("aa3".parse(), Ok(CellRef::new(26, 2)));
+
+        let cell: CellRef = "$C$4".parse().unwrap();
+        assert_eq!(cell.pos(), (2, 3));
+        assert!(cell.col_absolute && cell.row_absolute);
+        assert_eq!(cell.to_string(), "$C$4");
+        assert_eq!("C$4".parse::<CellRef>().unwrap().to_string(), "C$4");
+
+        for s in [
+            "", "A", "1", "A0", "A-1", "1A", "A1B", "$$A1", "A$$1", "A 1",
+        ] {
+            assert!(s.parse::<CellRef>().is_err(), "{:?}", s);
+        }
+        assert!(format!("A{}0", usize::MAX).parse::<CellRef>().is_err());
+    }
+
+    #[test]
+    fn display_extremes() {
+        let cell = CellRef::new(usize::MAX, usize::MAX);
+        assert_eq!(cell.to_string().parse::<CellRef>().ok(), None);
+        assert!(cell
+            .to_string()
+            .ends_with(&format!("{}", usize::MAX as u128 + 1)));
+    }
+
+    #[test]
+    fn ranges() {
+        let range: CellRange = "C1:A3".parse().unwrap();
+        assert_eq!(range.min(), (0, 0));
+        assert_eq!(range.max(), (2, 2));
+        assert!(range.contains((1, 2)));
+        assert!(!range.contains((3, 0)));
+        assert_eq!(range.to_string(), "C1:A3");
+
+        let single: CellRange = "B2".parse().unwrap();
+        assert_eq!(RangeInclusive::from(single), (1, 1)..=(1, 1));
+        assert!("A1:".parse::<CellRange>().is_err());
+    }
+
+    #[test]
+    fn positions_in_sheets() {
+        let mut sheet = crate::sheet::Sheet::from_str("a\tb\n");
+        let b1: CellRef = "B1".parse().unwrap();
+        sheet.swap(CellRef::new(0, 0), b1);
+        assert_eq!(sheet.move_cell(b1, CellRef::new(0, 1)), None);
+        sheet.set(CellRef::new(2, 0), "c");
+        assert_eq!(sheet.content_at((0, 0)), Some("b"));
+        assert_eq!(sheet.content_at((0, 1)), Some("a"));
+        assert_eq!(sheet.take(CellRef::new(2, 0)), Some("c".to_owned()));
+    }
+}
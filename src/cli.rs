@@ -0,0 +1,593 @@
+//! Command line arguments, and the commands run without the editor, for use in scripts.
+
+use std::{
+    error, fmt, fs,
+    io::{self, stdin, stdout, BufWriter, Write},
+    path::Path,
+};
+
+use crate::cell_ref::CellRef;
+use crate::editor::read_parse_options;
+use crate::formula::Evaluation;
+use crate::sheet::{Format, Layout, ParseOptions, Sheet};
+use crate::util::write_atomically;
+
+pub const USAGE: &str = "\
+Usage: tab-o-txt [-R] [FILE | -]
+       tab-o-txt get FILE CELL
+       tab-o-txt set FILE CELL CONTENT
+       tab-o-txt fmt FILE
+       tab-o-txt convert [--from FORMAT] [--to FORMAT] IN OUT
+       tab-o-txt select [--from FORMAT] [--to FORMAT] FILE --cols COL,...
+       tab-o-txt check [--layout LAYOUT] FILE
+
+Formats are txt, tsv, csv, md, html and json, told by the extensions of files unless given.
+`-` stands for stdin or stdout, in txt unless given. Only txt, tsv and csv can be read.
+Columns are given by the names in their first rows, or by their numbers counting from 1;
+`#` before a number, as in `#2`, always gives the number, even if a column is named after it.
+Names containing commas cannot be given, so give their numbers instead.";
+
+/// What to do, as given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Edits a sheet, or writes it to stdout if not run in a terminal.
+    Edit(EditOptions),
+    /// Prints the value of a unit.
+    Get { file_path: String, cell: CellRef },
+    /// Changes the content of a unit, saving the file.
+    Set {
+        file_path: String,
+        cell: CellRef,
+        content: String,
+    },
+    /// Aligns the columns of a file in place.
+    Fmt { file_path: String },
+    /// Writes a sheet read in one format in another.
+    Convert {
+        input: String,
+        output: String,
+        from: Format,
+        to: Format,
+    },
+    /// Writes some columns of a sheet to stdout.
+    Select {
+        input: String,
+        cols: Vec<String>,
+        from: Format,
+        to: Format,
+    },
+    /// Reports problems in the text of a sheet.
+    Check {
+        file_path: String,
+        layout: Option<Layout>,
+    },
+    /// Prints how to use the program.
+    Help,
+}
+
+impl Command {
+    /// Parses the arguments following the name of the program.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, UsageError> {
+        let mut args = args.into_iter();
+        let first = match args.next() {
+            Some(arg) => arg,
+            None => return Ok(Command::Edit(EditOptions::default())),
+        };
+
+        let command = match first.as_str() {
+            "-h" | "--help" => Command::Help,
+            "get" => {
+                let [file_path, cell] = positional(args, &["FILE", "CELL"])?;
+                Command::Get {
+                    file_path,
+                    cell: parse_cell(&cell)?,
+                }
+            }
+            "set" => {
+                let [file_path, cell, content] = positional(args, &["FILE", "CELL", "CONTENT"])?;
+                Command::Set {
+                    file_path,
+                    cell: parse_cell(&cell)?,
+                    content,
+                }
+            }
+            "fmt" => {
+                let [file_path] = positional(args, &["FILE"])?;
+                Command::Fmt { file_path }
+            }
+            "convert" => {
+                let options = Options::parse(args, &["--from", "--to"])?;
+                let [input, output] = positional(options.paths.into_iter(), &["IN", "OUT"])?;
+
+                Command::Convert {
+                    from: input_format(&input, options.from)?,
+                    to: format_of(&output, options.to)?,
+                    input,
+                    output,
+                }
+            }
+            "select" => {
+                let options = Options::parse(args, &["--from", "--to", "--cols"])?;
+                let [input] = positional(options.paths.into_iter(), &["FILE"])?;
+                let cols = match options.cols {
+                    Some(cols) => cols.split(',').map(str::to_owned).collect(),
+                    None => return Err(UsageError("missing --cols".to_owned())),
+                };
+
+                Command::Select {
+                    from: input_format(&input, options.from)?,
+                    to: options.to.unwrap_or(Format::Text),
+                    input,
+                    cols,
+                }
+            }
+            "check" => {
+                let options = Options::parse(args, &["--layout"])?;
+                let [file_path] = positional(options.paths.into_iter(), &["FILE"])?;
+
+                Command::Check {
+                    file_path,
+                    layout: options.layout,
+                }
+            }
+            _ => Command::Edit(EditOptions::parse(std::iter::once(first).chain(args))?),
+        };
+
+        Ok(command)
+    }
+}
+
+/// How to open the editor.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EditOptions {
+    /// The file to edit, if any.
+    pub file_path: Option<String>,
+    /// The sheet is read from stdin, as with `-`.
+    pub from_stdin: bool,
+    /// The sheet is opened read-only, as with `-R`.
+    pub readonly: bool,
+}
+
+impl EditOptions {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, UsageError> {
+        let mut options = Self::default();
+        let mut files = 0;
+        let mut only_files = false;
+
+        for arg in args {
+            match arg.as_str() {
+                "--" if !only_files => only_files = true,
+                "-R" if !only_files => options.readonly = true,
+                "-" if !only_files => {
+                    options.from_stdin = true;
+                    files += 1;
+                }
+                s if s.starts_with('-') && !only_files => {
+                    return Err(UsageError(format!("unknown option `{}`", s)));
+                }
+                _ => {
+                    options.file_path = Some(arg);
+                    files += 1;
+                }
+            }
+        }
+        if files > 1 {
+            return Err(UsageError("only one file can be edited".to_owned()));
+        }
+
+        Ok(options)
+    }
+}
+
+/// Options of the subcommands, and the paths given along with them.
+#[derive(Debug, Default)]
+struct Options {
+    from: Option<Format>,
+    to: Option<Format>,
+    cols: Option<String>,
+    layout: Option<Layout>,
+    paths: Vec<String>,
+}
+
+impl Options {
+    /// Parses `args`, taking only the options in `allowed`.
+    fn parse(mut args: impl Iterator<Item = String>, allowed: &[&str]) -> Result<Self, UsageError> {
+        let mut options = Self::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                s if s.starts_with("--") && !allowed.contains(&s) => {
+                    return Err(UsageError(format!("unknown option `{}`", s)));
+                }
+                "--from" => options.from = Some(parse_format(args.next())?),
+                "--to" => options.to = Some(parse_format(args.next())?),
+                "--cols" => match args.next() {
+                    Some(cols) => options.cols = Some(cols),
+                    None => return Err(UsageError("missing COL".to_owned())),
+                },
+                "--layout" => match args.next().map(|s| s.parse()) {
+                    Some(Ok(layout)) => options.layout = Some(layout),
+                    Some(Err(e)) => return Err(UsageError(e)),
+                    None => return Err(UsageError("missing LAYOUT".to_owned())),
+                },
+                _ => options.paths.push(arg),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Takes exactly as many arguments as there are `names`.
+fn positional<const N: usize>(
+    args: impl Iterator<Item = String>,
+    names: &[&str; N],
+) -> Result<[String; N], UsageError> {
+    let args: Vec<String> = args.collect();
+
+    args.try_into().map_err(|args: Vec<String>| {
+        UsageError(if args.len() < N {
+            format!("missing {}", names[args.len()..].join(" "))
+        } else {
+            format!("unexpected argument `{}`", args[N])
+        })
+    })
+}
+
+fn parse_cell(s: &str) -> Result<CellRef, UsageError> {
+    s.parse().map_err(|e| UsageError(format!("{}", e)))
+}
+
+/// Parses the value of an option giving a format.
+fn parse_format(s: Option<String>) -> Result<Format, UsageError> {
+    match s {
+        Some(s) => s.parse().map_err(UsageError),
+        None => Err(UsageError("missing FORMAT".to_owned())),
+    }
+}
+
+/// Gets the format of the file at `path` to read from, if not `given`.
+fn input_format(path: &str, given: Option<Format>) -> Result<Format, UsageError> {
+    let format = format_of(path, given)?;
+    if !format.is_readable() {
+        return Err(UsageError(format!("cannot read {}", format)));
+    }
+
+    Ok(format)
+}
+
+/// Gets the format of the file at `path`, if not `given`. Stdin and stdout are in
+/// [`Format::Text`].
+fn format_of(path: &str, given: Option<Format>) -> Result<Format, UsageError> {
+    match given {
+        Some(format) => Ok(format),
+        None if path == "-" => Ok(Format::Text),
+        None => Format::from_path(path).ok_or_else(|| {
+            UsageError(format!(
+                "cannot tell the format of `{}`; give it with --from or --to",
+                path
+            ))
+        }),
+    }
+}
+
+/// Reads how the file at `file_path` is parsed, the way the editor does. View settings that
+/// cannot be read are warned about and done without.
+fn parse_options(file_path: &str) -> ParseOptions {
+    read_parse_options(file_path).unwrap_or_else(|e| {
+        eprintln!(
+            "Warning: cannot read the view settings of `{}`: {}",
+            file_path, e
+        );
+        ParseOptions::default()
+    })
+}
+
+/// Reads the sheet in the file at `file_path`, the way the editor reads it.
+fn read_sheet(file_path: &str) -> io::Result<(String, Sheet)> {
+    let buf = fs::read_to_string(file_path)?;
+    let sheet = Sheet::parse_with(&buf, &parse_options(file_path));
+
+    Ok((buf, sheet))
+}
+
+/// Reads a sheet in `format` from the file at `path`, or from stdin for `-`.
+fn read_input(path: &str, format: Format) -> io::Result<Sheet> {
+    let buf = match path {
+        "-" => io::read_to_string(stdin())?,
+        _ if format == Format::Text => return Ok(read_sheet(path)?.1),
+        _ => fs::read_to_string(path)?,
+    };
+
+    Sheet::from_str_as(&buf, format).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot read {}", format),
+        )
+    })
+}
+
+/// Writes a sheet in `format` to the file at `path`, or to stdout for `-`.
+fn write_output(path: &str, sheet: &Sheet, format: Format) -> io::Result<()> {
+    if path == "-" {
+        return sheet.write_as(&mut stdout().lock(), format);
+    }
+
+    write_atomically(Path::new(path), false, |file| {
+        let mut writer = BufWriter::new(file);
+        sheet.write_as(&mut writer, format)?;
+        writer.flush()
+    })
+}
+
+/// Writes the value of the unit at `cell` as a line: the value of a formula, or the content.
+/// An empty unit makes an empty line.
+pub fn get<W: Write>(file_path: &str, cell: CellRef, writer: &mut W) -> io::Result<()> {
+    let (_, sheet) = read_sheet(file_path)?;
+    let evaluation = Evaluation::new(&sheet);
+
+    match evaluation.value_at(cell.pos()) {
+        Some(value) => writeln!(writer, "{}", value),
+        None => writeln!(
+            writer,
+            "{}",
+            sheet.content_at(cell.pos()).unwrap_or_default()
+        ),
+    }
+}
+
+/// Changes the content of the unit at `cell`, saving the file. Empty content empties the unit.
+pub fn set(file_path: &str, cell: CellRef, content: &str) -> io::Result<()> {
+    let (_, mut sheet) = read_sheet(file_path)?;
+    // Only the unit changes. Clearing it leaves its row and column in place, even if empty.
+    sheet.set(cell.pos(), content.trim());
+
+    sheet.write_to_file(file_path, false)
+}
+
+/// Aligns the columns of the file at `file_path` with tabs, as wide as their units, saving it
+/// if anything changed.
+pub fn format(file_path: &str) -> io::Result<()> {
+    let (buf, mut sheet) = read_sheet(file_path)?;
+    sheet.set_layout(Layout::Aligned);
+    sheet.realign();
+
+    let mut formatted = vec![];
+    sheet.write_to(&mut formatted)?;
+    if formatted == buf.as_bytes() {
+        return Ok(());
+    }
+
+    sheet.write_to_file(file_path, false)
+}
+
+/// Reads a sheet in `from` from `input`, and writes it in `to` to `output`. Either may be `-`
+/// for stdin or stdout.
+pub fn convert(input: &str, output: &str, from: Format, to: Format) -> io::Result<()> {
+    let sheet = read_input(input, from)?;
+
+    write_output(output, &sheet, to)
+}
+
+/// Writes the columns of `cols` in the sheet read in `from` from `input`, in `to`, with their
+/// widths measured again. Formulas are written as their values, as their references may not
+/// be selected, and text is written in [`Layout::Aligned`].
+pub fn select<W: Write>(
+    input: &str,
+    cols: &[String],
+    from: Format,
+    to: Format,
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut sheet = read_input(input, from)?;
+    let indices = cols
+        .iter()
+        .map(|col| {
+            find_column(&sheet, col).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("no column `{}`", col))
+            })
+        })
+        .collect::<io::Result<Vec<usize>>>()?;
+
+    let evaluation = Evaluation::new(&sheet);
+    for &col in &indices {
+        for row in 0..sheet.size().1 {
+            if let Some(value) = evaluation.value_at((col, row)) {
+                sheet.set((col, row), &value.to_string());
+            }
+        }
+    }
+
+    let mut selected = sheet.select_columns(&indices);
+    if to == Format::Text {
+        selected.set_layout(Layout::Aligned);
+    }
+
+    selected.write_as(writer, to)
+}
+
+/// Finds the column named `name` in the first row, or else numbered `name` counting from 1.
+/// `#` followed by a number only ever gives the column of that number.
+fn find_column(sheet: &Sheet, name: &str) -> Option<usize> {
+    let cols = sheet.size().0;
+    let column_of = |number: &str| {
+        number
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .filter(|&col| col < cols)
+    };
+
+    if let Some(number) = name.strip_prefix('#') {
+        return column_of(number);
+    }
+
+    (0..cols)
+        .find(|&col| sheet.content_at((col, 0)) == Some(name))
+        .or_else(|| column_of(name))
+}
+
+/// Writes the problems found in the text of the file at `file_path`, one per line, checking
+/// units against aligned columns unless `layout` gives another layout. Fails if there are any.
+pub fn check<W: Write>(file_path: &str, layout: Option<Layout>, writer: &mut W) -> io::Result<()> {
+    let buf = fs::read_to_string(file_path)?;
+    let options = ParseOptions {
+        layout,
+        ..parse_options(file_path)
+    };
+
+    let issues = Sheet::lint(&buf, &options);
+    for issue in &issues {
+        writeln!(writer, "{}:{}", file_path, issue)?;
+    }
+
+    match issues.len() {
+        0 => Ok(()),
+        1 => Err(io::Error::other("found 1 problem")),
+        n => Err(io::Error::other(format!("found {} problems", n))),
+    }
+}
+
+/// Error when the command line cannot be parsed. Contains what is wrong with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl error::Error for UsageError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, UsageError> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn subcommands() {
+        assert_eq!(
+            parse(&["get", "a.txt", "b2"]),
+            Ok(Command::Get {
+                file_path: "a.txt".to_owned(),
+                cell: CellRef::new(1, 1),
+            })
+        );
+        assert_eq!(
+            parse(&["set", "a.txt", "A1", ""]),
+            Ok(Command::Set {
+                file_path: "a.txt".to_owned(),
+                cell: CellRef::new(0, 0),
+                content: String::new(),
+            })
+        );
+        assert_eq!(
+            parse(&["convert", "--to", "json", "a.csv", "-"]),
+            Ok(Command::Convert {
+                input: "a.csv".to_owned(),
+                output: "-".to_owned(),
+                from: Format::Csv,
+                to: Format::Json,
+            })
+        );
+        assert_eq!(
+            parse(&["select", "a.txt", "--cols", "name,2"]),
+            Ok(Command::Select {
+                input: "a.txt".to_owned(),
+                cols: vec!["name".to_owned(), "2".to_owned()],
+                from: Format::Text,
+                to: Format::Text,
+            })
+        );
+        assert_eq!(
+            parse(&["check", "--layout", "elastic", "a.txt"]),
+            Ok(Command::Check {
+                file_path: "a.txt".to_owned(),
+                layout: Some(Layout::Elastic),
+            })
+        );
+        assert_eq!(parse(&["--help"]), Ok(Command::Help));
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert!(parse(&["get", "a.txt"]).is_err());
+        assert!(parse(&["get", "a.txt", "A0"]).is_err());
+        assert!(parse(&["fmt", "a.txt", "b.txt"]).is_err());
+        assert!(parse(&["convert", "a.txt", "b.xyz"]).is_err());
+        assert!(parse(&["convert", "a.md", "b.txt"]).is_err());
+        assert!(parse(&["convert", "--from"]).is_err());
+        assert!(parse(&["select", "a.txt"]).is_err());
+        assert!(parse(&["check", "--layout", "tabs", "a.txt"]).is_err());
+        assert!(parse(&["a.txt", "b.txt"]).is_err());
+        assert!(parse(&["-x"]).is_err());
+    }
+
+    #[test]
+    fn edit_options() {
+        assert_eq!(parse(&[]), Ok(Command::Edit(EditOptions::default())));
+        assert_eq!(
+            parse(&["-R", "-"]),
+            Ok(Command::Edit(EditOptions {
+                file_path: None,
+                from_stdin: true,
+                readonly: true,
+            }))
+        );
+        assert_eq!(
+            parse(&["--", "-R"]),
+            Ok(Command::Edit(EditOptions {
+                file_path: Some("-R".to_owned()),
+                ..EditOptions::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn set_keeps_empty_rows() {
+        let dir = std::env::temp_dir().join(format!("tab-o-txt-cli-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("s.txt");
+        let path = path.to_str().unwrap();
+        fs::write(path, "a\tb\n\tc\nd\te\n").unwrap();
+
+        set(path, CellRef::new(1, 1), "").unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "a\tb\n\nd\te\n");
+        set(path, CellRef::new(2, 3), " f ").unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "a\tb\n\nd\te\n\t\tf\n");
+
+        let mut buf = vec![];
+        get(path, CellRef::new(2, 3), &mut buf).unwrap();
+        assert_eq!(buf, b"f\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn select_writes_values() {
+        let dir = std::env::temp_dir().join(format!("tab-o-txt-select-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("s.tsv");
+        let path = path.to_str().unwrap();
+        fs::write(path, "name\t3\tsum\nx\t1\t=B2+1\nlonger\t3\t=B3*2\n").unwrap();
+
+        let select_to = |cols: &[&str], to| {
+            let cols: Vec<String> = cols.iter().map(|&col| col.to_owned()).collect();
+            let mut buf = vec![];
+            select(path, &cols, Format::Tsv, to, &mut buf).map(|_| String::from_utf8(buf).unwrap())
+        };
+        assert_eq!(
+            select_to(&["sum", "name"], Format::Text).unwrap(),
+            "sum\tname\n2\tx\n6\tlonger\n"
+        );
+        assert_eq!(select_to(&["3"], Format::Csv).unwrap(), "3\n1\n3\n");
+        assert_eq!(select_to(&["#3"], Format::Csv).unwrap(), "sum\n2\n6\n");
+        assert!(select_to(&["#4"], Format::Csv).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,16 +1,32 @@
-use std::fs::File;
-use std::io::{self, stdin, stdout, Write};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, stdin, stdout, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant, SystemTime};
 
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use crossterm::style::{Attribute, Print, ResetColor, SetAttribute};
 use crossterm::terminal::{Clear, ClearType};
 use crossterm::{cursor, event, execute, terminal};
-
-use sheet::Sheet;
 use unicode_width::UnicodeWidthStr;
 
-use crate::sheet;
-use crate::util::{is_in_offset_bounds, read_line_initial_text};
+use formula::Evaluation;
+use regex::Regex;
+use sheet::{Collation, Comparison, Condition, Filter, ParseOptions, RangeView, Sheet, SortKey};
+
+use crate::cell_ref::CellRef;
+use crate::cli::EditOptions;
+use crate::util::{
+    is_in_offset_bounds, is_process_running, is_writable, read_line_from_terminal,
+    read_line_initial_text, truncate_to_width, write_atomically,
+};
+use crate::value::CellType;
+use crate::{formula, sheet};
 
 pub struct Editor {
     mode: Mode,
@@ -20,6 +36,63 @@ pub struct Editor {
     pos: (usize, usize),
     /// From where the table starts to be drawn. Zero-indexed. Represented in `(col, row)`.
     corner: (usize, usize),
+    /// How many columns and rows stay displayed at the left and the top, however the table is
+    /// scrolled. Represented in `(cols, rows)`.
+    frozen: (usize, usize),
+    /// How wide columns are displayed, where overridden.
+    col_widths: HashMap<usize, ColumnWidth>,
+    /// Columns not displayed.
+    hidden: HashSet<usize>,
+    /// Where the selection started, if any. Zero-indexed. Represented in `(col, row)`.
+    anchor: Option<(usize, usize)>,
+    /// Rows not matching all of the filters are hidden.
+    filters: Vec<Filter>,
+    /// Whether the first row is shown regardless of the filters.
+    filter_header: bool,
+    /// Values of formulas, displayed in place of their contents.
+    evaluation: Evaluation,
+    /// Lines of text displayed in place of the sheet in [`Mode::Panel`].
+    panel: Vec<String>,
+    /// Index of the first line of the panel displayed.
+    panel_scroll: usize,
+    /// Whether saving keeps the previous file, with a `~` appended to its name.
+    backup: bool,
+    /// Shown in the status bar until the next key is pressed, such as why saving failed.
+    message: Option<String>,
+    /// Count of changes made to the sheet.
+    edits: usize,
+    /// Count of changes when the file was last saved.
+    saved_edits: usize,
+    /// Count of changes when the swap file was last written.
+    swapped_edits: usize,
+    /// When the swap file was last written, or writing it was tried.
+    swapped_at: Instant,
+    /// Whether the swap file is left alone rather than written over or removed, being another
+    /// editor's, or one that could not be read or recovered.
+    foreign_swap: bool,
+    /// When the last key was pressed.
+    last_key: Instant,
+    /// The sheet as found in a swap file left behind, until it is recovered or discarded.
+    recovery: Option<Sheet>,
+    /// Changes are saved after no key is pressed for this long, if set.
+    autosave_delay: Option<Duration>,
+    /// Changes are saved once there are this many not saved, if set.
+    autosave_edits: Option<usize>,
+    /// After autosaving failed, when it may be tried again, and how long was waited for that.
+    autosave_retry: Option<(Instant, Duration)>,
+    /// What the file looked like when last read or written, to tell if it changed since.
+    disk: Option<FileStamp>,
+    /// The sheet as last read from or written to the file, to merge changes made to the file
+    /// since.
+    base: Option<Sheet>,
+    /// Changes to the sheet are refused, as is saving it.
+    readonly: bool,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor::new()
+    }
 }
 
 impl Editor {
@@ -30,23 +103,128 @@ impl Editor {
             sheet: Sheet::new(),
             pos: (0, 0),
             corner: (0, 0),
+            frozen: (0, 0),
+            col_widths: HashMap::new(),
+            hidden: HashSet::new(),
+            anchor: None,
+            filters: vec![],
+            filter_header: false,
+            evaluation: Evaluation::default(),
+            panel: vec![],
+            panel_scroll: 0,
+            backup: false,
+            message: None,
+            edits: 0,
+            saved_edits: 0,
+            swapped_edits: 0,
+            swapped_at: Instant::now(),
+            foreign_swap: false,
+            last_key: Instant::now(),
+            recovery: None,
+            autosave_delay: None,
+            autosave_edits: None,
+            autosave_retry: None,
+            disk: None,
+            base: None,
+            readonly: false,
         }
     }
 
-    pub fn from(args: &[String]) -> io::Result<Self> {
-        let file_path = args.get(1).cloned();
+    pub fn from(options: &EditOptions) -> io::Result<Self> {
+        let mut file_path = options.file_path.clone();
+        let mut readonly = options.readonly;
+        let mut from_stdin = options.from_stdin;
+        // A sheet piped in without a file is read as with `-`. It has no file to be saved to
+        // until one is given.
+        if from_stdin || (file_path.is_none() && !stdin().is_terminal()) {
+            file_path = None;
+            from_stdin = true;
+        }
+        // Files that cannot be written to are still opened, but only to be viewed.
+        if let Some(f) = &file_path {
+            readonly |= !is_writable(Path::new(f));
+        }
+
+        // View settings that cannot be read are done without, rather than keeping the file
+        // from being opened.
+        let (view, view_error) = match file_path.as_deref().map(read_view) {
+            Some(Ok(view)) => (view, None),
+            Some(Err(e)) => (vec![], Some(e)),
+            None => (vec![], None),
+        };
+        let options = parse_options(&view);
         let sheet = match &file_path {
-            Some(f) => Sheet::from_file(f)?,
+            Some(f) => Sheet::parse_with(&fs::read_to_string(f)?, &options),
+            None if from_stdin => Sheet::parse_with(&io::read_to_string(stdin())?, &options),
             None => Sheet::new(),
         };
 
-        Ok(Self {
-            mode: Mode::Navigate,
+        // Changes in a swap file cannot be recovered without changing the sheet, nor while
+        // another editor is still making them. Such swap files are left alone, as are those that
+        // cannot be read.
+        let mut recovery = None;
+        let mut foreign_swap = false;
+        let mut swap_message = None;
+        match file_path
+            .as_deref()
+            .map(|f| fs::read_to_string(swap_path(f)))
+        {
+            Some(Ok(buf)) => {
+                let (owner, buf) = parse_swap(&buf);
+                let running = owner.as_ref().and_then(SwapOwner::is_running);
+                if let (Some(owner), Some(true)) = (&owner, running) {
+                    readonly = true;
+                    swap_message = Some(format!(
+                        "Opened read-only, as process {} is editing the file",
+                        owner.pid
+                    ));
+                }
+                if readonly {
+                    foreign_swap = true;
+                } else {
+                    recovery = Some(Sheet::parse_with(buf, &options));
+                }
+            }
+            Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => {
+                foreign_swap = true;
+                swap_message = Some(format!("Cannot read the swap file: {}", e));
+            }
+            _ => (),
+        }
+
+        let mut editor = Self {
             file_path,
             sheet,
-            pos: (0, 0),
-            corner: (0, 0),
-        })
+            readonly,
+            foreign_swap,
+            ..Self::new()
+        };
+        for cmd in &view {
+            editor.apply_view_command(cmd);
+        }
+        editor.evaluation = Evaluation::new(&editor.sheet);
+        editor.edits = 0;
+        if let Some(e) = view_error {
+            editor.message = Some(format!("Cannot read the view settings: {}", e));
+        }
+        if swap_message.is_some() {
+            editor.message = swap_message;
+        }
+        if let Some(f) = &editor.file_path {
+            editor.disk = FileStamp::read(f)?;
+            editor.base = Some(editor.sheet.clone());
+        }
+        if recovery.is_some() {
+            editor.recovery = recovery;
+            editor.mode = Mode::Recover;
+        }
+
+        Ok(editor)
+    }
+
+    /// Writes the sheet as it would be saved, without editing it.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.sheet.write_to(writer)
     }
 
     pub fn run(&mut self) -> io::Result<()> {
@@ -66,6 +244,14 @@ impl Editor {
                     terminal::disable_raw_mode()?;
                     self.command()?;
                 }
+                Mode::Panel => {
+                    terminal::enable_raw_mode()?;
+                    self.panel()?;
+                }
+                Mode::Recover => {
+                    terminal::enable_raw_mode()?;
+                    self.recover()?;
+                }
                 Mode::Quit => {
                     terminal::disable_raw_mode()?;
                     self.quit()?;
@@ -73,25 +259,69 @@ impl Editor {
                     break;
                 }
             }
+
+            if self.has_autosave_edits() {
+                self.autosave()?;
+            }
         }
 
         Ok(())
     }
 
+    /// Writes the changes to the swap file, and autosaves them, once they are due. Returns how
+    /// long until they next are, if ever.
+    fn idle(&mut self) -> io::Result<Option<Duration>> {
+        let mut timeouts = vec![];
+
+        if self.edits != self.swapped_edits && !self.readonly && !self.foreign_swap {
+            match SWAP_DELAY.checked_sub(self.swapped_at.elapsed()) {
+                Some(timeout) if !timeout.is_zero() => timeouts.push(timeout),
+                _ => {
+                    if let Err(e) = self.write_swap() {
+                        self.message = Some(format!("Cannot write the swap file: {}", e));
+                    }
+                    self.swapped_at = Instant::now();
+                }
+            }
+        }
+
+        if let Some(delay) = self.autosave_delay {
+            if self.edits != self.saved_edits {
+                let retry = self.autosave_retry.map_or(Duration::ZERO, |(at, _)| {
+                    at.saturating_duration_since(Instant::now())
+                });
+                let timeout = delay.saturating_sub(self.last_key.elapsed()).max(retry);
+                if timeout.is_zero() {
+                    self.autosave()?;
+                } else {
+                    timeouts.push(timeout);
+                }
+            }
+        }
+
+        Ok(timeouts.into_iter().min())
+    }
+
     fn navigate(&mut self) -> io::Result<()> {
+        // Done before drawing, so that what it reports is shown.
+        let timeout = self.idle()?;
         self.refresh()?;
 
+        let rows = self.view_rows();
+        let accum = self.accum_display_widths();
+        let display_content = self.display_content(self.pos);
+        let offset = display_content
+            .as_ref()
+            .map_or(0, |s| self.align_offset(self.pos, s));
         execute!(
             stdout(),
             cursor::MoveTo(
-                ((self.sheet.accum_width_at(self.pos.0).unwrap()
-                    - self.sheet.accum_width_at(self.corner.0).unwrap())
-                    * self.sheet.tab_size()) as u16,
-                (self.pos.1 - self.corner.1) as u16,
+                (self.display_x(&accum, self.pos.0).unwrap_or(0) + offset) as u16,
+                self.display_y(&rows, self.pos.1).unwrap_or(0) as u16,
             )
         )?;
 
-        if let Some(s) = self.sheet.content_at(self.pos) {
+        if let Some(s) = display_content {
             execute!(
                 stdout(),
                 SetAttribute(Attribute::Reverse),
@@ -100,7 +330,16 @@ impl Editor {
             )?;
         }
 
+        // Wait for a key only until changes are due to be kept in the swap file or saved.
+        if let Some(timeout) = timeout {
+            if !event::poll(timeout)? {
+                return Ok(());
+            }
+        }
+
         if let Event::Key(event) = event::read()? {
+            self.last_key = Instant::now();
+            self.message = None;
             match event {
                 KeyEvent {
                     code: KeyCode::Up, ..
@@ -146,13 +385,16 @@ impl Editor {
                     code: KeyCode::PageDown,
                     ..
                 } => {
-                    self.move_pos_by(0, (terminal::size().unwrap().1 - 1) as isize)?;
+                    self.move_pos_by(0, (terminal::size().unwrap().1.saturating_sub(1)) as isize)?;
                 }
                 KeyEvent {
                     code: KeyCode::PageUp,
                     ..
                 } => {
-                    self.move_pos_by(0, -((terminal::size().unwrap().1 - 1) as isize))?;
+                    self.move_pos_by(
+                        0,
+                        -((terminal::size().unwrap().1.saturating_sub(1)) as isize),
+                    )?;
                 }
 
                 KeyEvent {
@@ -162,16 +404,30 @@ impl Editor {
                     self.mode = Mode::Command;
                 }
 
+                KeyEvent {
+                    code: KeyCode::Char('v'),
+                    ..
+                } => {
+                    self.anchor = match self.anchor {
+                        Some(_) => None,
+                        None => Some(self.pos),
+                    };
+                }
+
                 KeyEvent {
                     code: KeyCode::Esc, ..
                 } => {
-                    self.mode = Mode::Quit;
+                    if self.anchor.is_some() {
+                        self.anchor = None;
+                    } else {
+                        self.mode = Mode::Quit;
+                    }
                 }
 
                 KeyEvent {
                     code: KeyCode::F(2),
                     ..
-                } => {
+                } if self.allows_changes() => {
                     self.mode = Mode::Edit;
                 }
 
@@ -184,30 +440,65 @@ impl Editor {
 
     fn move_pos_by(&mut self, x: isize, y: isize) -> io::Result<()> {
         let size = terminal::size()?;
+        self.move_pos_on_screen(x, y, (size.0 as usize, size.1 as usize));
+
+        Ok(())
+    }
 
+    /// Moves the cursor as [`Editor::move_pos_by`] does, on a screen of `size`, scrolling so
+    /// that it stays in view.
+    fn move_pos_on_screen(&mut self, x: isize, y: isize, size: (usize, usize)) {
         self.pos.0 = self
             .pos
             .0
             .saturating_add_signed(x)
             .clamp(0, self.sheet.size().0);
-        self.pos.1 = self
-            .pos
-            .1
-            .saturating_add_signed(y)
-            .clamp(0, self.sheet.size().1);
 
-        if !is_in_offset_bounds(
-            *self.sheet.accum_width_at(self.pos.0).unwrap(),
-            *self.sheet.accum_width_at(self.pos.0).unwrap(),
-            (size.0 as usize - 1) / self.sheet.tab_size(),
-        ) {
+        // Hidden columns are stepped over, backwards only as far as the first column.
+        let mut step = if x < 0 { -1 } else { 1 };
+        while self.is_hidden(self.pos.0) {
+            if self.pos.0 == 0 {
+                step = 1;
+            }
+            self.pos.0 = self.pos.0.saturating_add_signed(step);
+        }
+
+        // Only the columns after the frozen ones are scrolled through.
+        let rows = self.view_rows();
+        let frozen = self.frozen(&rows);
+        let accum = self.accum_display_widths();
+        let width = size
+            .0
+            .saturating_sub(1 + self.accum_display_width(&accum, frozen.0));
+        if self.pos.0 >= frozen.0
+            && !is_in_offset_bounds(
+                self.accum_display_width(&accum, self.pos.0),
+                self.accum_display_width(&accum, self.corner.0),
+                width.max(1),
+            )
+        {
             self.corner.0 = self.corner.0.saturating_add_signed(x);
         }
-        if !is_in_offset_bounds(self.pos.1, self.corner.1, size.1 as usize - 1) {
-            self.corner.1 = self.corner.1.saturating_add_signed(y);
+
+        self.corner.0 = self.corner.0.min(self.pos.0).max(frozen.0);
+
+        // Rows are moved through by their indices among those displayed.
+        let height = size.1.saturating_sub(1 + frozen.1).max(1);
+        let index = index_of(&rows, self.pos.1)
+            .saturating_add_signed(y)
+            .min(rows.len() - 1);
+        let mut corner = index_of(&rows, self.corner.1);
+        if index >= frozen.1 {
+            if !is_in_offset_bounds(index, corner, height) {
+                corner = corner.saturating_add_signed(y);
+            }
+            // Keep the cursor on screen, e.g. after the rows before it got hidden.
+            corner = corner.clamp(index.saturating_sub(height.saturating_sub(1)), index);
         }
+        let corner = corner.max(frozen.1).min(rows.len() - 1);
 
-        Ok(())
+        self.pos.1 = rows[index];
+        self.corner.1 = rows[corner];
     }
 
     fn edit(&mut self) -> io::Result<()> {
@@ -215,9 +506,37 @@ impl Editor {
             Some(s) => s.to_owned(),
             None => String::new(),
         };
-        buf = read_line_initial_text(&buf)?;
+        // The content is typed over from the start of the column, whatever is displayed.
+        let accum = self.accum_display_widths();
+        execute!(
+            stdout(),
+            cursor::MoveTo(
+                (self.display_x(&accum, self.pos.0).unwrap_or(0)
+                    + UnicodeWidthStr::width(buf.as_str())) as u16,
+                self.display_y(&self.view_rows(), self.pos.1).unwrap_or(0) as u16,
+            )
+        )?;
+        // Changes made before are still kept in the swap file and autosaved while typing.
+        buf = read_line_initial_text(&buf, |last_key| {
+            self.last_key = last_key;
+            self.idle()
+        })?;
+        self.last_key = Instant::now();
 
+        if self.sheet.content_at(self.pos).unwrap_or_default() != buf.trim() {
+            self.edits += 1;
+        }
+        let size = self.sheet.size();
         self.sheet.edit(self.pos, &buf);
+        if self.sheet.size().0 < size.0 {
+            self.remove_col_settings(self.pos.0);
+        }
+        // Positions of other units only stay the same if no row or column got removed.
+        if self.sheet.size().0 >= size.0 && self.sheet.size().1 >= size.1 {
+            self.evaluation.update(&self.sheet, self.pos);
+        } else {
+            self.evaluation = Evaluation::new(&self.sheet);
+        }
 
         self.mode = Mode::Navigate;
 
@@ -227,39 +546,187 @@ impl Editor {
     fn command(&mut self) -> io::Result<()> {
         execute!(
             stdout(),
-            cursor::MoveTo(0, terminal::size().unwrap().1 - 1),
+            cursor::MoveTo(0, terminal::size().unwrap().1.saturating_sub(1)),
             Print(":")
         )?;
 
-        let mut command = String::new();
-        stdin().read_line(&mut command)?;
+        let command = read_line_from_terminal()?;
 
         self.parse_command(command.trim())?;
 
         Ok(())
     }
 
+    fn panel(&mut self) -> io::Result<()> {
+        self.print_panel()?;
+
+        if let Event::Key(event) = event::read()? {
+            if !self.scroll_panel(event.code)? {
+                self.panel.clear();
+                self.panel_scroll = 0;
+                execute!(stdout(), Clear(ClearType::All))?;
+                self.mode = Mode::Navigate;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Asks what to do with the sheet found in a swap file.
+    fn recover(&mut self) -> io::Result<()> {
+        if self.panel.is_empty() {
+            self.panel = vec![
+                "A swap file was found. The editor may have crashed while editing the file,"
+                    .to_owned(),
+                "or may still be editing it.".to_owned(),
+                "Press `r` to recover the changes in it, `d` to see how they differ,".to_owned(),
+                "`x` to discard them, or `q` to quit.".to_owned(),
+            ];
+        }
+        self.print_panel()?;
+
+        let recovery = match &self.recovery {
+            Some(recovery) => recovery,
+            None => {
+                self.mode = Mode::Navigate;
+                return Ok(());
+            }
+        };
+
+        if let Event::Key(event) = event::read()? {
+            match event.code {
+                KeyCode::Char('r') => {
+                    self.sheet = self.recovery.take().unwrap();
+                    self.evaluation = Evaluation::new(&self.sheet);
+                    self.edits += 1;
+                    self.message = Some("Recovered from the swap file".to_owned());
+                    self.mode = Mode::Navigate;
+                }
+                KeyCode::Char('d') => {
+                    let diff = diff_sheets(&self.sheet, recovery);
+                    self.panel.truncate(4);
+                    self.panel.push(String::new());
+                    if diff.is_empty() {
+                        self.panel.push("No differences.".to_owned());
+                    }
+                    self.panel.extend(diff);
+                }
+                KeyCode::Char('x') => {
+                    if let Some(file_path) = &self.file_path {
+                        fs::remove_file(swap_path(file_path))?;
+                    }
+                    self.recovery = None;
+                    self.mode = Mode::Navigate;
+                }
+                KeyCode::Char('q') => {
+                    self.mode = Mode::Quit;
+                }
+                code => {
+                    self.scroll_panel(code)?;
+                }
+            }
+        }
+
+        if !matches!(self.mode, Mode::Recover) {
+            self.panel.clear();
+            self.panel_scroll = 0;
+            execute!(stdout(), Clear(ClearType::All))?;
+        }
+
+        Ok(())
+    }
+
+    fn print_panel(&self) -> io::Result<()> {
+        let height = terminal::size()?.1 as usize;
+        execute!(stdout(), Clear(ClearType::All))?;
+        for (i, line) in self
+            .panel
+            .iter()
+            .skip(self.panel_scroll)
+            .take(height)
+            .enumerate()
+        {
+            execute!(stdout(), cursor::MoveTo(0, i as u16), Print(line))?;
+        }
+
+        Ok(())
+    }
+
+    /// Scrolls the panel if `code` is for scrolling. Returns whether it is.
+    fn scroll_panel(&mut self, code: KeyCode) -> io::Result<bool> {
+        let height = terminal::size()?.1 as usize;
+        let max_scroll = self.panel.len().saturating_sub(height);
+
+        match code {
+            KeyCode::Up => self.panel_scroll = self.panel_scroll.saturating_sub(1),
+            KeyCode::Down => self.panel_scroll = (self.panel_scroll + 1).min(max_scroll),
+            KeyCode::PageUp => self.panel_scroll = self.panel_scroll.saturating_sub(height),
+            KeyCode::PageDown => self.panel_scroll = (self.panel_scroll + height).min(max_scroll),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
     fn quit(&self) -> io::Result<()> {
         execute!(stdout(), terminal::LeaveAlternateScreen)?;
 
+        // Changes not saved by now are given up. A swap file is only removed if it was written
+        // here, not if it was left behind and not dealt with, or is another editor's.
+        if let (Some(file_path), true) = (
+            &self.file_path,
+            self.swapped_edits > 0 && !self.foreign_swap,
+        ) {
+            match fs::remove_file(swap_path(file_path)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+
         Ok(())
     }
 
     fn print(&self) -> io::Result<()> {
         let size: (u16, u16) = terminal::size()?;
 
-        let cols = self.corner.0..self.corner.0 + (size.0 as usize - 1) / self.sheet.tab_size();
-        for col in cols {
-            for row in self.corner.1..self.corner.1 + size.1 as usize - 1 {
-                if let Some(s) = self.sheet.content_at((col, row)) {
-                    let (display_col, display_row) =
-                        self.sheet.get_display_pos((col, row), self.corner);
+        let rows = self.view_rows();
+        let frozen = self.frozen(&rows);
+        let corner = (
+            self.corner.0.max(frozen.0),
+            index_of(&rows, self.corner.1).max(frozen.1),
+        );
+        let visible_rows = rows[..frozen.1].iter().chain(&rows[corner.1..]);
+        let accum = self.accum_display_widths();
+
+        let cols = (0..frozen.0).chain(corner.0..self.sheet.size().0);
+        for col in cols.filter(|&col| !self.is_hidden(col)) {
+            let display_col = self.display_x(&accum, col).unwrap();
+            if display_col >= size.0 as usize {
+                break;
+            }
+
+            for (display_row, &row) in visible_rows
+                .clone()
+                .take((size.1 as usize).saturating_sub(1))
+                .enumerate()
+            {
+                if let Some(s) = self.display_content((col, row)) {
+                    let display_col = display_col + self.align_offset((col, row), &s);
 
                     execute!(
                         stdout(),
                         cursor::MoveTo(display_col as u16, display_row as u16),
-                        Print(s),
                     )?;
+                    if self.is_selected((col, row)) {
+                        execute!(
+                            stdout(),
+                            SetAttribute(Attribute::Underlined),
+                            Print(s),
+                            SetAttribute(Attribute::Reset),
+                        )?;
+                    } else {
+                        execute!(stdout(), Print(s))?;
+                    }
                 }
             }
         }
@@ -267,6 +734,173 @@ impl Editor {
         Ok(())
     }
 
+    /// Gets how many columns and rows are frozen, at most as many as there are. Represented in
+    /// `(cols, rows)`.
+    fn frozen(&self, rows: &[usize]) -> (usize, usize) {
+        (
+            self.frozen.0.min(self.sheet.size().0),
+            self.frozen.1.min(rows.len() - 1),
+        )
+    }
+
+    /// Gets where the column of `col` is drawn from the left, if it is not scrolled out.
+    /// `accum` is as given by [`Editor::accum_display_widths`].
+    fn display_x(&self, accum: &[usize], col: usize) -> Option<usize> {
+        let frozen = self.frozen.0.min(self.sheet.size().0);
+        let corner = self.corner.0.max(frozen);
+
+        if col < frozen {
+            Some(self.accum_display_width(accum, col))
+        } else if col >= corner {
+            Some(
+                self.accum_display_width(accum, frozen) + self.accum_display_width(accum, col)
+                    - self.accum_display_width(accum, corner),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Gets how many characters of the units in the column of `col` are displayed.
+    fn content_width(&self, col: usize) -> usize {
+        let width = self.sheet.width_at(col).unwrap_or(&1) * self.sheet.tab_size() - 1;
+
+        match self.col_widths.get(&col) {
+            Some(ColumnWidth::Fixed(n)) => *n,
+            Some(ColumnWidth::Min(n)) => width.max(*n),
+            Some(ColumnWidth::Max(n)) => width.min(*n),
+            None => width,
+        }
+    }
+
+    /// Gets how many characters the column of `col` takes on the screen, including the gap
+    /// after it.
+    fn display_width(&self, col: usize) -> usize {
+        if self.is_hidden(col) {
+            0
+        } else {
+            self.content_width(col) + 1
+        }
+    }
+
+    /// Gets how many characters the columns before each column take on the screen, up to that
+    /// past the end of the sheet. Measured once for all columns, to be looked up with
+    /// [`Editor::accum_display_width`].
+    fn accum_display_widths(&self) -> Vec<usize> {
+        let mut accum = vec![0];
+        for col in 0..self.sheet.size().0 {
+            accum.push(accum[col] + self.display_width(col));
+        }
+
+        accum
+    }
+
+    /// Gets how many characters the columns before that of `col` take on the screen. `accum`
+    /// is as given by [`Editor::accum_display_widths`].
+    fn accum_display_width(&self, accum: &[usize], col: usize) -> usize {
+        match accum.get(col) {
+            Some(&width) => width,
+            None => {
+                let last = accum.len() - 1;
+                accum[last]
+                    + (last..col)
+                        .map(|col| self.display_width(col))
+                        .sum::<usize>()
+            }
+        }
+    }
+
+    /// Moves back the settings of the columns after that of `col`, which got removed.
+    fn remove_col_settings(&mut self, col: usize) {
+        let shift = |c: usize| if c > col { c - 1 } else { c };
+        self.hidden = self
+            .hidden
+            .drain()
+            .filter(|&c| c != col)
+            .map(shift)
+            .collect();
+        self.col_widths = self
+            .col_widths
+            .drain()
+            .filter(|&(c, _)| c != col)
+            .map(|(c, width)| (shift(c), width))
+            .collect();
+    }
+
+    /// Checks if the column of `col` is hidden. Columns past the end of the sheet never are.
+    fn is_hidden(&self, col: usize) -> bool {
+        col < self.sheet.size().0 && self.hidden.contains(&col)
+    }
+
+    /// Gets where the row of `row` is drawn from the top, if it is not scrolled out.
+    fn display_y(&self, rows: &[usize], row: usize) -> Option<usize> {
+        let frozen = self.frozen(rows).1;
+        let corner = index_of(rows, self.corner.1).max(frozen);
+        let index = index_of(rows, row);
+
+        if index < frozen {
+            Some(index)
+        } else if index >= corner {
+            Some(frozen + index - corner)
+        } else {
+            None
+        }
+    }
+
+    /// Gets what to display for the unit at `pos`: the value of a formula, or the content, cut
+    /// to the width of its column.
+    fn display_content(&self, pos: (usize, usize)) -> Option<String> {
+        let s = match self.evaluation.value_at(pos) {
+            Some(value) => value.to_string(),
+            None => self.sheet.content_at(pos)?.to_owned(),
+        };
+
+        Some(truncate_to_width(&s, self.content_width(pos.0)).to_owned())
+    }
+
+    /// Gets how far to the right `s`, displayed for the unit at `pos`, is drawn. Numbers are
+    /// aligned to the right of their columns, everything else to the left.
+    fn align_offset(&self, pos: (usize, usize), s: &str) -> usize {
+        let is_number = match self.evaluation.value_at(pos) {
+            Some(value) => matches!(value, formula::Value::Number(_)),
+            None => self.sheet.value_at(pos).is_some_and(|v| v.is_number()),
+        };
+        if !is_number {
+            return 0;
+        }
+
+        self.content_width(pos.0)
+            .saturating_sub(UnicodeWidthStr::width(s))
+    }
+
+    /// Gets the number at `pos` for statistics, that of a formula being its value.
+    fn number_at(&self, pos: (usize, usize)) -> Option<f64> {
+        match self.evaluation.value_at(pos) {
+            Some(formula::Value::Number(n)) => Some(*n),
+            Some(_) => None,
+            None => self.sheet.value_at(pos)?.as_number(),
+        }
+    }
+
+    /// Gets the upper left and lower right corners of the selection, if any.
+    fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        self.anchor.map(|anchor| {
+            (
+                (anchor.0.min(self.pos.0), anchor.1.min(self.pos.1)),
+                (anchor.0.max(self.pos.0), anchor.1.max(self.pos.1)),
+            )
+        })
+    }
+
+    fn is_selected(&self, pos: (usize, usize)) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                (start.0..=end.0).contains(&pos.0) && (start.1..=end.1).contains(&pos.1)
+            }
+            None => false,
+        }
+    }
+
     fn refresh(&self) -> io::Result<()> {
         execute!(
             stdout(),
@@ -277,73 +911,1438 @@ impl Editor {
         )?;
 
         self.print()?;
+        self.print_status()?;
+
+        Ok(())
+    }
+
+    fn print_status(&self) -> io::Result<()> {
+        let size: (u16, u16) = terminal::size()?;
+
+        let mut status = vec![CellRef::from(self.pos).to_string()];
+        if let Some(message) = &self.message {
+            status.push(message.to_owned());
+        }
+        if self.readonly {
+            status.push("read-only".to_owned());
+        }
+        if !self.filters.is_empty() {
+            let hidden = self.sheet.size().1 + 1 - self.view_rows().len();
+            status.push(format!("filtered: {} rows hidden", hidden));
+        }
+        let visible: HashSet<usize> = self.view_rows().into_iter().collect();
+        let stats = self
+            .stats_range()
+            .stats_with(|row| visible.contains(&row), |pos| self.number_at(pos));
+        if let (Some(min), Some(max), Some(mean)) = (stats.min, stats.max, stats.mean()) {
+            status.push(format!(
+                "sum {} count {} min {} max {} mean {} distinct {}",
+                format_number(stats.sum),
+                stats.count,
+                format_number(min),
+                format_number(max),
+                format_number(mean),
+                stats.distinct,
+            ));
+        }
+
+        execute!(
+            stdout(),
+            cursor::MoveTo(0, size.1.saturating_sub(1)),
+            Clear(ClearType::CurrentLine),
+            // Wrapping past the last line would scroll the whole screen.
+            Print(truncate_to_width(
+                &status.join(" | "),
+                (size.0 as usize).saturating_sub(1)
+            )),
+        )?;
 
         Ok(())
     }
 
+    /// Gets the range the statistics in the status bar are about: the selection, or the cursor
+    /// column.
+    fn stats_range(&self) -> RangeView<'_> {
+        match self.selection() {
+            Some((start, end)) => self.sheet.range(start..=end),
+            None => self
+                .sheet
+                .range((self.pos.0, 0)..=(self.pos.0, self.sheet.size().1.saturating_sub(1))),
+        }
+    }
+
+    /// Opens a panel summarizing each column in the selection, or every column.
+    fn stats(&mut self) {
+        let (start, end) = self.selection().unwrap_or((
+            (0, 0),
+            (
+                self.sheet.size().0.saturating_sub(1),
+                self.sheet.size().1.saturating_sub(1),
+            ),
+        ));
+
+        let visible: HashSet<usize> = self.view_rows().into_iter().collect();
+        let mut lines = vec![];
+        for col in start.0..=end.0 {
+            let summary = self
+                .sheet
+                .range((col, start.1)..=(col, end.1))
+                .summary_with(5, |row| visible.contains(&row), |pos| self.number_at(pos));
+            let types: Vec<String> = summary
+                .types
+                .iter()
+                .map(|(ty, n)| format!("{} {}", ty, n))
+                .collect();
+            let top_values: Vec<String> = summary
+                .top_values
+                .iter()
+                .map(|(content, n)| format!("{} ({})", content, n))
+                .collect();
+
+            lines.push(format!("Column {}", CellRef::col_name(col)));
+            lines.push(format!(
+                "  units: {}, empty: {}",
+                summary.units, summary.empty
+            ));
+            lines.push(format!("  types: {}", types.join(", ")));
+            let stats = &summary.stats;
+            if let (Some(min), Some(max), Some(mean)) = (stats.min, stats.max, stats.mean()) {
+                lines.push(format!(
+                    "  numbers: count {}, sum {}, min {}, max {}, mean {}, distinct {}",
+                    stats.count,
+                    format_number(stats.sum),
+                    format_number(min),
+                    format_number(max),
+                    format_number(mean),
+                    stats.distinct,
+                ));
+            }
+            lines.push(format!("  top values: {}", top_values.join(", ")));
+            lines.push(String::new());
+        }
+
+        self.panel = lines;
+        self.panel_scroll = 0;
+    }
+
+    /// Gets the rows to be displayed, in order. The row past the end of the sheet is always
+    /// included, so that new rows can be appended.
+    fn view_rows(&self) -> Vec<usize> {
+        let mut rows = if self.filters.is_empty() {
+            (0..self.sheet.size().1).collect()
+        } else {
+            self.sheet.filter_rows(&self.filters)
+        };
+        if self.filter_header && rows.first() != Some(&0) {
+            rows.insert(0, 0);
+        }
+        rows.push(self.sheet.size().1);
+
+        rows
+    }
+
     fn parse_command(&mut self, cmd: &str) -> io::Result<()> {
-        let mut iter = cmd.chars();
+        let mut words = cmd.split_whitespace();
 
-        while let Some(c) = iter.next() {
-            match c {
-                'w' => {
-                    self.save()?;
-                    self.mode = Mode::Navigate;
+        match words.next() {
+            Some("layout") => {
+                if let Some(Ok(layout)) = words.next().map(str::parse) {
+                    self.sheet.set_layout(layout);
                 }
-                'q' => {
-                    self.mode = Mode::Quit;
+                self.mode = Mode::Navigate;
+            }
+            Some("filter") => {
+                self.filter(split_word(cmd).1);
+                self.move_pos_by(0, 0)?;
+                self.mode = Mode::Navigate;
+            }
+            Some("goto") => {
+                if let Some(Ok(cell)) = words.next().map(str::parse::<CellRef>) {
+                    self.goto(cell.pos())?;
                 }
-                _ => {
-                    self.mode = Mode::Navigate;
+                self.mode = Mode::Navigate;
+            }
+            Some("colwidth") => {
+                self.set_col_width(words);
+                self.move_pos_by(0, 0)?;
+                self.mode = Mode::Navigate;
+            }
+            Some(cmd @ ("hide" | "show")) => {
+                self.set_hidden(words, cmd == "hide");
+                self.move_pos_by(0, 0)?;
+                self.mode = Mode::Navigate;
+            }
+            Some("freeze") => {
+                self.freeze(words);
+                self.move_pos_by(0, 0)?;
+                self.mode = Mode::Navigate;
+            }
+            Some("stats") => {
+                self.stats();
+                self.mode = Mode::Panel;
+            }
+            Some("set") => {
+                self.set(words);
+                self.move_pos_by(0, 0)?;
+                self.mode = Mode::Navigate;
+            }
+            Some("view") => {
+                match words.next() {
+                    Some("save") => self.save_view()?,
+                    Some("delete") => self.delete_view()?,
+                    _ => (),
+                }
+                self.mode = Mode::Navigate;
+            }
+            Some("type") => {
+                // Types are only viewed, and never change the file.
+                if self.allows_changes() {
+                    self.set_type(words);
+                    self.evaluation = Evaluation::new(&self.sheet);
+                }
+                self.mode = Mode::Navigate;
+            }
+            Some("sort") => {
+                if self.allows_changes() {
+                    match self.sort(words) {
+                        Ok(()) => {
+                            self.edits += 1;
+                            self.evaluation = Evaluation::new(&self.sheet);
+                        }
+                        Err(e) => self.message = Some(format!("Cannot sort: {}", e)),
+                    }
+                }
+                self.mode = Mode::Navigate;
+            }
+            Some("e!") => {
+                if let Err(e) = self.reload() {
+                    self.message = Some(format!("Cannot reload: {}", e));
                 }
+                self.move_pos_by(0, 0)?;
+                self.mode = Mode::Navigate;
+            }
+            Some("merge") => {
+                if self.allows_changes() {
+                    if let Err(e) = self.merge() {
+                        self.message = Some(format!("Cannot merge: {}", e));
+                    }
+                }
+                self.move_pos_by(0, 0)?;
+                self.mode = Mode::Navigate;
+            }
+            Some(word) => {
+                // With `!`, the file is overwritten even if it changed since it was read.
+                let (word, force) = match word.strip_suffix('!') {
+                    Some(word) => (word, true),
+                    None => (word, false),
+                };
+                for c in word.chars() {
+                    match c {
+                        'w' => {
+                            self.mode = Mode::Navigate;
+                            if let Err(e) = self.save(force) {
+                                self.message = Some(format!("Cannot save: {}", e));
+                                break;
+                            }
+                        }
+                        'q' => {
+                            self.mode = Mode::Quit;
+                        }
+                        _ => {
+                            self.mode = Mode::Navigate;
+                        }
+                    }
+                }
+            }
+            None => {
+                self.mode = Mode::Navigate;
             }
         }
 
         Ok(())
     }
 
-    fn save(&self) -> io::Result<()> {
-        let file_path = match &self.file_path {
-            Some(fp) => fp.to_owned(),
-            None => {
-                execute!(stdout(), Print("New file. Save as: "))?;
+    /// Moves the cursor to `pos`.
+    fn goto(&mut self, pos: (usize, usize)) -> io::Result<()> {
+        let rows = self.view_rows();
+        let x = pos.0 as isize - self.pos.0 as isize;
+        let y = index_of(&rows, pos.1) as isize - index_of(&rows, self.pos.1) as isize;
+
+        self.move_pos_by(x, y)
+    }
 
-                let mut buf = String::new();
-                stdin().read_line(&mut buf)?;
-                buf.trim().to_owned()
+    /// Sorts the selected rows, or all rows if nothing is selected, by the cursor column or by
+    /// given keys. A key is written as a column, optionally followed by `:` and flags: `d` for
+    /// descending, `n` for numeric, `v` for natural and `t` for typed order. `header` keeps the
+    /// first row in place when sorting all rows. Fails without sorting on keys it cannot read.
+    fn sort<'a>(&mut self, args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+        let mut keep_header = false;
+        let mut keys = vec![];
+
+        for arg in args {
+            if arg == "header" {
+                keep_header = true;
+                continue;
             }
-        };
-        let mut file = File::options()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(file_path)?;
-
-        for row in 0..self.sheet.size().1 {
-            let mut count: usize = 0;
-            for col in 0..self.sheet.size().0 {
-                if let Some(s) = self.sheet.content_at((col, row)) {
-                    file.write_all(&b"\t".repeat(count))?;
-                    file.write_all(s.as_bytes())?;
-
-                    let width = UnicodeWidthStr::width(s) / self.sheet.tab_size() + 1;
-                    count = 1 + (self.sheet.width_at(col).unwrap() - width);
-                } else {
-                    count += self.sheet.width_at(col).unwrap();
-                }
+
+            let (col, flags) = arg.split_once(':').unwrap_or((arg, ""));
+            let mut key = match parse_col(col) {
+                Some(col) => SortKey::new(col),
+                None => return Err(format!("unknown column `{}`", col)),
+            };
+            for flag in flags.chars() {
+                key = match flag {
+                    'd' => key.descending(),
+                    'n' => key.collation(Collation::Numeric),
+                    'v' => key.collation(Collation::Natural),
+                    't' => key.collation(Collation::Typed),
+                    _ => return Err(format!("unknown flag `{}` in `{}`", flag, arg)),
+                };
             }
-            file.write_all(b"\n")?;
+            keys.push(key);
+        }
+
+        if keys.is_empty() {
+            keys.push(SortKey::new(self.pos.0));
+        }
+
+        match self.selection() {
+            Some((start, end)) => self.sheet.sort_rows_in(start.1..end.1 + 1, &keys),
+            None => self.sheet.sort_rows_by(&keys, keep_header),
         }
 
         Ok(())
     }
-}
 
-#[derive(Debug, Default)]
-enum Mode {
+    /// Freezes columns and rows, written as `rows=<n>` and `cols=<n>`. Without arguments,
+    /// nothing is frozen any more.
+    fn freeze<'a>(&mut self, args: impl Iterator<Item = &'a str>) {
+        let mut frozen = (0, 0);
+
+        for arg in args {
+            let (key, n) = match arg.split_once('=').map(|(key, n)| (key, n.parse())) {
+                Some((key, Ok(n))) => (key, n),
+                _ => return,
+            };
+            match key {
+                "cols" => frozen.0 = n,
+                "rows" => frozen.1 = n,
+                _ => return,
+            }
+        }
+
+        self.frozen = frozen;
+    }
+
+    /// Sets options, written as `<name>=<value>`: `tabsize`, how wide tabs are assumed to be,
+    /// `comment`, the prefix of lines kept as comments, or nothing for no comments, and
+    /// `autosave` and `autosaveedits`, after how many seconds without a key pressed or how many
+    /// changes to save, or 0 not to. `backup` and `nobackup` turn on or off keeping the previous
+    /// file when saving. `readonly` and `noreadonly` turn on or off refusing changes, though the
+    /// sheet stays read-only while another editor has a swap file of it, or if it cannot be
+    /// written to.
+    fn set<'a>(&mut self, args: impl Iterator<Item = &'a str>) {
+        for arg in args {
+            match arg {
+                "backup" => self.backup = true,
+                "nobackup" => self.backup = false,
+                "readonly" => self.readonly = true,
+                "noreadonly" if self.foreign_swap => {
+                    self.message = Some(
+                        "The sheet stays read-only, as another editor has a swap file of it"
+                            .to_owned(),
+                    );
+                }
+                "noreadonly"
+                    if self
+                        .file_path
+                        .as_ref()
+                        .is_some_and(|f| !is_writable(Path::new(f))) =>
+                {
+                    self.message =
+                        Some("The sheet stays read-only, as it cannot be written to".to_owned());
+                }
+                "noreadonly" => self.readonly = false,
+                _ => (),
+            }
+            match arg.split_once('=') {
+                Some(("tabsize" | "comment", _)) if !self.allows_changes() => (),
+                Some(("tabsize", n)) => match n.parse() {
+                    Ok(n) if n != self.sheet.tab_size() => {
+                        self.sheet.set_tab_size(n);
+                        self.edits += 1;
+                    }
+                    _ => (),
+                },
+                Some(("autosave", n)) => match n.parse() {
+                    Ok(0) => self.autosave_delay = None,
+                    Ok(n) => self.autosave_delay = Some(Duration::from_secs(n)),
+                    Err(_) => (),
+                },
+                Some(("autosaveedits", n)) => match n.parse() {
+                    Ok(0) => self.autosave_edits = None,
+                    Ok(n) => self.autosave_edits = Some(n),
+                    Err(_) => (),
+                },
+                Some(("comment", prefix)) => {
+                    let prefix = Some(prefix).filter(|prefix| !prefix.is_empty());
+                    if prefix != self.sheet.comment_prefix() {
+                        // Rows become comments and back, moving the units after them. The
+                        // text only changes if formulas refer to units that moved.
+                        let mut before = vec![];
+                        let mut after = vec![];
+                        let _ = self.sheet.write_to(&mut before);
+                        self.sheet.set_comment_prefix(prefix);
+                        let _ = self.sheet.write_to(&mut after);
+                        if before != after {
+                            self.edits += 1;
+                        }
+                        self.pos.1 = self.pos.1.min(self.sheet.size().1.saturating_sub(1));
+                        self.corner.1 = self.corner.1.min(self.pos.1);
+                        self.evaluation = Evaluation::new(&self.sheet);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Overrides how wide the cursor column, or a given column, is displayed, as in
+    /// `colwidth C 20`. The width is written as a number of characters, optionally after `min=`
+    /// or `max=`. `auto` displays the column as wide as its contents again.
+    fn set_col_width<'a>(&mut self, mut args: impl Iterator<Item = &'a str>) {
+        let (col, width) = match (args.next(), args.next()) {
+            (Some(col), Some(width)) => (parse_col(col), width),
+            (Some(width), None) => (Some(self.pos.0), width),
+            _ => return,
+        };
+        let width = match width.split_once('=') {
+            _ if width == "auto" => None,
+            Some(("min", n)) => n.parse().ok().map(ColumnWidth::Min),
+            Some(("max", n)) => n.parse().ok().map(ColumnWidth::Max),
+            Some(_) => return,
+            None => match width.parse() {
+                Ok(n) => Some(ColumnWidth::Fixed(n)),
+                Err(_) => return,
+            },
+        };
+
+        if let Some(col) = col {
+            match width {
+                Some(width) => self.col_widths.insert(col, width),
+                None => self.col_widths.remove(&col),
+            };
+        }
+    }
+
+    /// Hides or shows the cursor column, or given columns. Without arguments, `show` shows
+    /// every column.
+    fn set_hidden<'a>(&mut self, args: impl Iterator<Item = &'a str>, hidden: bool) {
+        let cols: Option<Vec<usize>> = args.map(parse_col).collect();
+        let cols = match cols {
+            Some(cols) if !cols.is_empty() => cols,
+            Some(_) if !hidden => {
+                self.hidden.clear();
+                return;
+            }
+            Some(_) => vec![self.pos.0],
+            None => return,
+        };
+
+        for col in cols {
+            if hidden {
+                self.hidden.insert(col);
+            } else {
+                self.hidden.remove(&col);
+            }
+        }
+    }
+
+    /// Declares the type of the cursor column, or of a given column, as in `type C date`.
+    /// `auto` lets the types be inferred again.
+    fn set_type<'a>(&mut self, mut args: impl Iterator<Item = &'a str>) {
+        let (col, ty) = match (args.next(), args.next()) {
+            (Some(col), Some(ty)) => (parse_col(col), ty),
+            (Some(ty), None) => (Some(self.pos.0), ty),
+            _ => return,
+        };
+        let ty = match ty {
+            "auto" => None,
+            ty => match ty.parse::<CellType>() {
+                Ok(ty) => Some(ty),
+                Err(_) => return,
+            },
+        };
+
+        if let Some(col) = col {
+            self.sheet.set_column_type(col, ty);
+        }
+    }
+
+    /// Adds a filter, written as a column, an operator and a value.
+    /// Operators are `=` for equal to, `~` for containing, `=~` for matching a regular
+    /// expression, and `==`, `!=`, `<`, `<=`, `>`, `>=` for comparing numbers. `header` keeps the
+    /// first row shown. Without arguments, all filters are removed.
+    ///
+    /// `args` is the text after the command, as typed, so that the value is the rest of it with
+    /// its spaces kept. A value in double quotes is taken without them, `\"` and `\\` standing
+    /// for `"` and `\`, so that it can also start or end with spaces.
+    fn filter(&mut self, args: &str) {
+        let (mut arg, mut rest) = split_word(args);
+        if arg.is_empty() {
+            self.filters.clear();
+            self.filter_header = false;
+            return;
+        }
+        if arg == "header" {
+            self.filter_header = true;
+            (arg, rest) = split_word(rest);
+        }
+
+        let col = match parse_col(arg) {
+            Some(col) => col,
+            None => return,
+        };
+        let (op, value) = split_word(rest);
+        let value = unquote(value).unwrap_or_else(|| value.to_owned());
+
+        let number = value.parse::<f64>();
+        let condition = match (op, number) {
+            ("=", _) => Condition::Equals(value),
+            ("~", _) => Condition::Contains(value),
+            ("=~", _) => match Regex::new(&value) {
+                Ok(regex) => Condition::Matches(regex),
+                Err(_) => return,
+            },
+            ("==", Ok(n)) => Condition::Compare(Comparison::Equal, n),
+            ("!=", Ok(n)) => Condition::Compare(Comparison::NotEqual, n),
+            ("<", Ok(n)) => Condition::Compare(Comparison::Less, n),
+            ("<=", Ok(n)) => Condition::Compare(Comparison::LessEqual, n),
+            (">", Ok(n)) => Condition::Compare(Comparison::Greater, n),
+            (">=", Ok(n)) => Condition::Compare(Comparison::GreaterEqual, n),
+            _ => return,
+        };
+
+        self.filters.push(Filter::new(col, condition));
+    }
+
+    /// Applies a command read from the view settings of the file. Only commands changing how
+    /// the sheet is viewed are recognized.
+    fn apply_view_command(&mut self, cmd: &str) {
+        let mut words = cmd.split_whitespace();
+
+        match words.next() {
+            // Only options of how the sheet is viewed are kept, and not those of the editor.
+            Some("set") => self.set(
+                words.filter(|arg| matches!(arg.split_once('='), Some(("tabsize" | "comment", _)))),
+            ),
+            Some("freeze") => self.freeze(words),
+            Some("colwidth") => self.set_col_width(words),
+            Some("hide") => self.set_hidden(words, true),
+            Some("filter") => self.filter(split_word(cmd).1),
+            Some("type") => self.set_type(words),
+            _ => (),
+        }
+    }
+
+    /// Gets the commands restoring how the sheet is viewed, one per line of the view settings.
+    fn view_commands(&self) -> Vec<String> {
+        let mut cmds = vec![format!("set tabsize={}", self.sheet.tab_size())];
+        if let Some(prefix) = self.sheet.comment_prefix() {
+            cmds.push(format!("set comment={}", prefix));
+        }
+
+        if self.frozen != (0, 0) {
+            cmds.push(format!(
+                "freeze rows={} cols={}",
+                self.frozen.1, self.frozen.0
+            ));
+        }
+
+        let mut col_widths: Vec<_> = self.col_widths.iter().collect();
+        col_widths.sort_by_key(|(&col, _)| col);
+        for (&col, width) in col_widths {
+            let width = match width {
+                ColumnWidth::Fixed(n) => n.to_string(),
+                ColumnWidth::Min(n) => format!("min={}", n),
+                ColumnWidth::Max(n) => format!("max={}", n),
+            };
+            cmds.push(format!("colwidth {} {}", CellRef::col_name(col), width));
+        }
+
+        if !self.hidden.is_empty() {
+            let mut hidden: Vec<usize> = self.hidden.iter().copied().collect();
+            hidden.sort_unstable();
+            let names: Vec<String> = hidden.into_iter().map(CellRef::col_name).collect();
+            cmds.push(format!("hide {}", names.join(" ")));
+        }
+
+        for (i, filter) in self.filters.iter().enumerate() {
+            let (op, value) = match &filter.condition {
+                Condition::Equals(value) => ("=", quote(value)),
+                Condition::Contains(value) => ("~", quote(value)),
+                Condition::Matches(regex) => ("=~", quote(regex.as_str())),
+                Condition::Compare(comparison, n) => {
+                    let op = match comparison {
+                        Comparison::Equal => "==",
+                        Comparison::NotEqual => "!=",
+                        Comparison::Less => "<",
+                        Comparison::LessEqual => "<=",
+                        Comparison::Greater => ">",
+                        Comparison::GreaterEqual => ">=",
+                    };
+                    (op, n.to_string())
+                }
+            };
+            let header = if i == 0 && self.filter_header {
+                "header "
+            } else {
+                ""
+            };
+            cmds.push(format!(
+                "filter {}{} {} {}",
+                header,
+                CellRef::col_name(filter.col),
+                op,
+                value
+            ));
+        }
+
+        for col in 0..self.sheet.size().0 {
+            if let Some(ty) = self.sheet.column_type(col) {
+                cmds.push(format!("type {} {}", CellRef::col_name(col), ty));
+            }
+        }
+
+        cmds
+    }
+
+    /// Saves the view settings next to the file, so that it opens the same way next time.
+    fn save_view(&self) -> io::Result<()> {
+        if let Some(file_path) = &self.file_path {
+            let mut buf = self.view_commands().join("\n");
+            buf.push('\n');
+            write_atomically(&view_path(file_path), false, |file| {
+                file.write_all(buf.as_bytes())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the view settings of the file, if any.
+    fn delete_view(&self) -> io::Result<()> {
+        if let Some(file_path) = &self.file_path {
+            match fs::remove_file(view_path(file_path)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the sheet to the swap file if it has changes not saved, or removes the swap file
+    /// otherwise. A read-only sheet keeps no changes there, and the swap file of another editor
+    /// is left alone.
+    fn write_swap(&mut self) -> io::Result<()> {
+        let file_path = match &self.file_path {
+            Some(_) if self.readonly || self.foreign_swap => return Ok(()),
+            Some(file_path) => file_path,
+            None => return Ok(()),
+        };
+
+        if self.edits == self.saved_edits {
+            match fs::remove_file(swap_path(file_path)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        } else {
+            write_atomically(&swap_path(file_path), false, |file| {
+                writeln!(file, "{}", SwapOwner::current())?;
+                self.sheet.write_to(file)
+            })?;
+        }
+        self.swapped_edits = self.edits;
+
+        Ok(())
+    }
+
+    /// Checks if the sheet may be changed. Tells why not if it is read-only.
+    fn allows_changes(&mut self) -> bool {
+        if self.readonly {
+            self.message =
+                Some("The sheet is read-only; `:set noreadonly` allows changes".to_owned());
+        }
+
+        !self.readonly
+    }
+
+    /// Checks if as many changes were made since saving as `:set autosaveedits` asks to autosave
+    /// after.
+    fn has_autosave_edits(&self) -> bool {
+        self.autosave_edits
+            .is_some_and(|n| self.edits - self.saved_edits >= n)
+    }
+
+    /// Saves the changes, if the file has a path to save to. After failing, saving is tried
+    /// less and less often, and failing again is not reported again.
+    fn autosave(&mut self) -> io::Result<()> {
+        if self.file_path.is_none() || self.edits == self.saved_edits {
+            return Ok(());
+        }
+        if self
+            .autosave_retry
+            .is_some_and(|(at, _)| Instant::now() < at)
+        {
+            return Ok(());
+        }
+
+        match self.save(false) {
+            Ok(_) => self.message = Some("Autosaved".to_owned()),
+            Err(e) => {
+                let wait = match self.autosave_retry {
+                    Some((_, wait)) => (wait * 2).min(AUTOSAVE_MAX_RETRY),
+                    None => {
+                        self.message = Some(format!("Cannot autosave: {}", e));
+                        AUTOSAVE_RETRY
+                    }
+                };
+                self.autosave_retry = Some((Instant::now() + wait, wait));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the sheet from the file again, giving up the changes made to it.
+    fn reload(&mut self) -> io::Result<()> {
+        let file_path = match &self.file_path {
+            Some(file_path) => file_path.to_owned(),
+            None => return Ok(()),
+        };
+
+        let sheet = self.read_file(&file_path)?;
+        self.sheet = sheet;
+        self.evaluation = Evaluation::new(&self.sheet);
+        self.disk = FileStamp::read(&file_path)?;
+        self.base = Some(self.sheet.clone());
+        self.saved_edits = self.edits;
+        self.write_swap()?;
+        self.message = Some(format!("\"{}\" reloaded", file_path));
+
+        Ok(())
+    }
+
+    /// Merges the changes made to the file since it was read into the sheet. Where both
+    /// changed a unit, the content in the sheet is kept.
+    fn merge(&mut self) -> io::Result<()> {
+        let (file_path, base) = match (&self.file_path, &self.base) {
+            (Some(file_path), Some(base)) => (file_path.to_owned(), base),
+            _ => return Ok(()),
+        };
+
+        let theirs = self.read_file(&file_path)?;
+        let conflicts = self.sheet.merge(base, &theirs);
+        self.evaluation = Evaluation::new(&self.sheet);
+        self.disk = FileStamp::read(&file_path)?;
+        self.base = Some(theirs);
+        self.edits += 1;
+
+        self.message = Some(if conflicts.is_empty() {
+            "Merged the changes in the file".to_owned()
+        } else {
+            let cells: Vec<String> = conflicts
+                .into_iter()
+                .map(|pos| CellRef::from(pos).to_string())
+                .collect();
+            format!(
+                "Merged the changes in the file, keeping yours in {}",
+                cells.join(", ")
+            )
+        });
+
+        Ok(())
+    }
+
+    /// Reads the sheet from the file at `file_path`, the way the current sheet was read.
+    fn read_file(&self, file_path: &str) -> io::Result<Sheet> {
+        let options = ParseOptions {
+            layout: None,
+            tab_size: self.sheet.tab_size(),
+            comment_prefix: self.sheet.comment_prefix().map(str::to_owned),
+        };
+        let mut sheet = Sheet::parse_with(&fs::read_to_string(file_path)?, &options);
+        for col in 0..self.sheet.size().0 {
+            if let Some(ty) = self.sheet.column_type(col) {
+                sheet.set_column_type(col, Some(ty));
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    /// Checks if the file changed since it was last read or written.
+    fn is_changed_on_disk(&self) -> io::Result<bool> {
+        let (file_path, disk) = match (&self.file_path, &self.disk) {
+            (Some(file_path), Some(disk)) => (file_path, disk),
+            _ => return Ok(false),
+        };
+
+        // Reading the whole file is only needed if it looks changed.
+        match fs::metadata(file_path) {
+            Ok(metadata)
+                if metadata.len() == disk.len && metadata.modified().ok() == disk.modified =>
+            {
+                Ok(false)
+            }
+            Ok(_) => Ok(FileStamp::read(file_path)?.is_some_and(|s| s.hash != disk.hash)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Saves the sheet to the file, unless the file changed since it was read and `force` is
+    /// not set.
+    fn save(&mut self, force: bool) -> io::Result<()> {
+        if self.readonly {
+            return Err(io::Error::other(
+                "the sheet is read-only; `:set noreadonly` allows saving it",
+            ));
+        }
+        if !force && self.is_changed_on_disk()? {
+            return Err(io::Error::other(
+                "the file changed since it was read; see `:w!`, `:e!` and `:merge`",
+            ));
+        }
+
+        let file_path = match &self.file_path {
+            Some(fp) => fp.to_owned(),
+            None => {
+                execute!(stdout(), Print("New file. Save as: "))?;
+
+                read_line_from_terminal()?.trim().to_owned()
+            }
+        };
+
+        self.sheet.write_to_file(&file_path, self.backup)?;
+        self.message = Some(format!("\"{}\" written", file_path));
+        self.disk = FileStamp::read(&file_path)?;
+        self.base = Some(self.sheet.clone());
+        self.file_path = Some(file_path);
+        self.saved_edits = self.edits;
+        self.autosave_retry = None;
+        self.write_swap()?;
+
+        // View settings are only kept up to date once they have been saved.
+        if self
+            .file_path
+            .as_ref()
+            .is_some_and(|f| view_path(f).exists())
+        {
+            self.save_view()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Gets the path of the view settings of the file at `file_path`: a hidden file next to it,
+/// such as `.data.txt.tabotxt` for `data.txt`.
+fn view_path(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    path.with_file_name(format!(".{}.tabotxt", name))
+}
+
+/// Gets the path of the swap file of the file at `file_path`, holding changes not saved yet: a
+/// hidden file next to it, such as `.data.txt.swp` for `data.txt`.
+fn swap_path(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    path.with_file_name(format!(".{}.swp", name))
+}
+
+/// How long changes may wait to be written to the swap file, so that it is not written after
+/// every change.
+const SWAP_DELAY: Duration = Duration::from_secs(2);
+
+/// How long to wait before autosaving again after it failed, the first time.
+const AUTOSAVE_RETRY: Duration = Duration::from_secs(5);
+/// How long to wait at most before autosaving again after it failed, however many times.
+const AUTOSAVE_MAX_RETRY: Duration = Duration::from_secs(300);
+
+/// Starts the first line of a swap file, naming the editor writing it.
+const SWAP_HEADER: &str = "tab-o-txt swap file of process ";
+
+/// The editor writing a swap file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SwapOwner {
+    pid: u32,
+    host: String,
+}
+
+impl SwapOwner {
+    fn current() -> Self {
+        Self {
+            pid: process::id(),
+            host: host_name(),
+        }
+    }
+
+    /// Checks if the editor is still running. Returns `None` if that cannot be told, such as
+    /// for editors on other hosts.
+    fn is_running(&self) -> Option<bool> {
+        if self.host != host_name() {
+            return None;
+        }
+
+        is_process_running(self.pid)
+    }
+}
+
+impl fmt::Display for SwapOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{} on {}", SWAP_HEADER, self.pid, self.host)
+    }
+}
+
+/// Splits a swap file into the editor writing it, if named, and the sheet.
+fn parse_swap(buf: &str) -> (Option<SwapOwner>, &str) {
+    let (line, rest) = buf.split_once('\n').unwrap_or((buf, ""));
+    let owner = line
+        .strip_prefix(SWAP_HEADER)
+        .and_then(|line| line.split_once(" on "))
+        .and_then(|(pid, host)| {
+            Some(SwapOwner {
+                pid: pid.parse().ok()?,
+                host: host.to_owned(),
+            })
+        });
+
+    match owner {
+        Some(owner) => (Some(owner), rest),
+        None => (None, buf),
+    }
+}
+
+/// Gets the name of the host, or an empty string if it is not known.
+fn host_name() -> String {
+    fs::read_to_string("/etc/hostname")
+        .ok()
+        .or_else(|| env::var("HOSTNAME").ok())
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_owned())
+        .unwrap_or_default()
+}
+
+/// Describes how the units of `new` differ from those of `old`, one unit per line.
+fn diff_sheets(old: &Sheet, new: &Sheet) -> Vec<String> {
+    let old_cells: BTreeMap<_, _> = old.cells().map(|(pos, s)| ((pos.1, pos.0), s)).collect();
+    let new_cells: BTreeMap<_, _> = new.cells().map(|(pos, s)| ((pos.1, pos.0), s)).collect();
+
+    let positions: BTreeSet<_> = old_cells.keys().chain(new_cells.keys()).collect();
+    positions
+        .into_iter()
+        .filter_map(|&(row, col)| {
+            let cell = CellRef::new(col, row);
+            match (old_cells.get(&(row, col)), new_cells.get(&(row, col))) {
+                (Some(a), Some(b)) if a != b => Some(format!("{}: {:?} -> {:?}", cell, a, b)),
+                (Some(a), None) => Some(format!("{}: {:?} removed", cell, a)),
+                (None, Some(b)) => Some(format!("{}: {:?} added", cell, b)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Gets how to read a file from its view settings, which may set the tab size and the comment
+/// prefix.
+fn parse_options(view: &[String]) -> ParseOptions {
+    let mut options = ParseOptions::default();
+
+    let args = view
+        .iter()
+        .filter_map(|cmd| cmd.strip_prefix("set "))
+        .flat_map(str::split_whitespace);
+    for arg in args {
+        match arg.split_once('=') {
+            Some(("tabsize", n)) => {
+                if let Ok(n) = n.parse() {
+                    options.tab_size = n;
+                }
+            }
+            Some(("comment", prefix)) => {
+                options.comment_prefix = Some(prefix.to_owned()).filter(|p| !p.is_empty());
+            }
+            _ => (),
+        }
+    }
+
+    options
+}
+
+/// Reads how the file at `file_path` is parsed, as saved with its view settings.
+pub(crate) fn read_parse_options(file_path: &str) -> io::Result<ParseOptions> {
+    Ok(parse_options(&read_view(file_path)?))
+}
+
+/// Reads the view settings of the file at `file_path`, one command per line. There are none if
+/// they have never been saved.
+fn read_view(file_path: &str) -> io::Result<Vec<String>> {
+    match fs::read_to_string(view_path(file_path)) {
+        Ok(buf) => Ok(buf.lines().map(str::to_owned).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes `s` in double quotes, escaping `"` and `\` with `\`.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Reads a value written by [`quote`]. Returns `None` if `s` is not written that way.
+fn unquote(s: &str) -> Option<String> {
+    let mut chars = s.strip_prefix('"')?.strip_suffix('"')?.chars();
+
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?),
+            '"' => return None,
+            c => value.push(c),
+        }
+    }
+
+    Some(value)
+}
+
+/// Parses a column given by its name, or by its number counting from 1.
+fn parse_col(s: &str) -> Option<usize> {
+    match s.parse::<usize>() {
+        Ok(n) => n.checked_sub(1),
+        Err(_) => CellRef::parse_col(s),
+    }
+}
+
+/// Formats a number the way formulas display their values.
+fn format_number(n: f64) -> String {
+    formula::Value::Number(n).to_string()
+}
+
+/// Splits the first word off `s`, returning it and the rest of `s` after the whitespace
+/// following it, as typed.
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(i) => (&s[..i], s[i..].trim_start()),
+        None => (s, ""),
+    }
+}
+
+/// Gets the index of `row` in `rows`, or of the first row after it if it is not in `rows`.
+fn index_of(rows: &[usize], row: usize) -> usize {
+    rows.binary_search(&row)
+        .unwrap_or_else(|i| i)
+        .min(rows.len() - 1)
+}
+
+/// What a file looked like at some point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl FileStamp {
+    /// Reads the file at `path`. Returns `None` if there is no such file.
+    fn read(path: &str) -> io::Result<Option<Self>> {
+        let buf = match fs::read(path) {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut hasher = DefaultHasher::new();
+        buf.hash(&mut hasher);
+
+        Ok(Some(Self {
+            modified: fs::metadata(path)?.modified().ok(),
+            len: buf.len() as u64,
+            hash: hasher.finish(),
+        }))
+    }
+}
+
+/// How wide a column is displayed, overriding the width of its contents. Given in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnWidth {
+    Fixed(usize),
+    Min(usize),
+    Max(usize),
+}
+
+#[derive(Debug, Default)]
+enum Mode {
     #[default]
     Navigate,
     Edit,
     Command,
+    /// Displays lines of text until a key other than for scrolling is pressed.
+    Panel,
+    /// Asks what to do with a swap file left behind.
+    Recover,
     Quit,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_owner() {
+        let owner = SwapOwner::current();
+        let buf = format!("{}\na\tb\n", owner);
+        assert_eq!(parse_swap(&buf), (Some(owner.clone()), "a\tb\n"));
+        assert_eq!(owner.is_running(), is_process_running(process::id()));
+
+        // Swap files written before they named their editors are read whole.
+        assert_eq!(parse_swap("a\tb\n"), (None, "a\tb\n"));
+        assert_eq!(parse_swap(SWAP_HEADER), (None, SWAP_HEADER));
+
+        let elsewhere = SwapOwner {
+            host: format!("{}.elsewhere", owner.host),
+            ..owner
+        };
+        assert_eq!(elsewhere.is_running(), None);
+    }
+
+    fn test_file(name: &str) -> String {
+        let dir = env::temp_dir().join(format!("tab-o-txt-editor-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("data.txt").to_str().unwrap().to_owned()
+    }
+
+    fn open(file_path: &str) -> Editor {
+        Editor::from(&EditOptions {
+            file_path: Some(file_path.to_owned()),
+            ..EditOptions::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn swap_recovery() {
+        let file_path = test_file("swap-recovery");
+        fs::write(&file_path, "a\tb\n").unwrap();
+        let elsewhere = SwapOwner {
+            host: format!("{}.elsewhere", host_name()),
+            ..SwapOwner::current()
+        };
+        fs::write(swap_path(&file_path), format!("{}\na\tc\n", elsewhere)).unwrap();
+
+        // The sheet is read from the file, and the changes left in the swap file are offered.
+        let editor = open(&file_path);
+        assert!(matches!(editor.mode, Mode::Recover));
+        assert!(!editor.readonly);
+        assert_eq!(editor.sheet.content_at((1, 0)), Some("b"));
+        let recovery = editor.recovery.as_ref().unwrap();
+        assert_eq!(recovery.content_at((1, 0)), Some("c"));
+        assert_eq!(recovery.size(), (2, 1));
+
+        // Swap files of editors still running are left to them.
+        if SwapOwner::current().is_running() == Some(true) {
+            fs::write(
+                swap_path(&file_path),
+                format!("{}\na\tc\n", SwapOwner::current()),
+            )
+            .unwrap();
+            let editor = open(&file_path);
+            assert!(matches!(editor.mode, Mode::Navigate));
+            assert!(editor.readonly && editor.foreign_swap);
+            assert!(editor.recovery.is_none());
+        }
+
+        fs::remove_dir_all(Path::new(&file_path).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn swap_written_when_idle() {
+        let file_path = test_file("swap-idle");
+        fs::write(&file_path, "a\tb\n").unwrap();
+        let mut editor = open(&file_path);
+        assert!(editor.idle().unwrap().is_none());
+
+        // Changes wait a while before they are written.
+        editor.sheet.set((1, 0), "c");
+        editor.edits += 1;
+        editor.swapped_at = Instant::now();
+        assert!(editor.idle().unwrap().is_some_and(|t| t <= SWAP_DELAY));
+        assert!(!swap_path(&file_path).exists());
+
+        editor.swapped_at = Instant::now() - SWAP_DELAY;
+        assert!(editor.idle().unwrap().is_none());
+        let buf = fs::read_to_string(swap_path(&file_path)).unwrap();
+        assert_eq!(parse_swap(&buf), (Some(SwapOwner::current()), "a\tc\n"));
+        assert_eq!(editor.swapped_edits, editor.edits);
+
+        // Once the changes are saved, or undone, the swap file is removed.
+        editor.saved_edits = editor.edits;
+        editor.write_swap().unwrap();
+        assert!(!swap_path(&file_path).exists());
+
+        // Read-only sheets keep no swap files.
+        editor.readonly = true;
+        editor.edits += 1;
+        editor.swapped_at = Instant::now() - SWAP_DELAY;
+        assert!(editor.idle().unwrap().is_none());
+        assert!(!swap_path(&file_path).exists());
+
+        fs::remove_dir_all(Path::new(&file_path).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn autosave_when_due() {
+        let file_path = test_file("autosave");
+        fs::write(&file_path, "a\tb\n").unwrap();
+        let mut editor = open(&file_path);
+        editor.autosave_delay = Some(Duration::from_secs(10));
+        editor.autosave_edits = Some(2);
+
+        editor.sheet.set((1, 0), "c");
+        editor.edits += 1;
+        editor.last_key = Instant::now();
+        assert!(editor.idle().unwrap().is_some());
+        assert!(!editor.has_autosave_edits());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "a\tb\n");
+
+        // Enough changes are saved at once, without waiting.
+        editor.sheet.set((1, 0), "d");
+        editor.edits += 1;
+        assert!(editor.has_autosave_edits());
+        editor.autosave().unwrap();
+        assert_eq!(editor.saved_edits, editor.edits);
+        assert_eq!(editor.message.as_deref(), Some("Autosaved"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "a\td\n");
+
+        // Fewer changes are saved once no key has been pressed for a while.
+        editor.sheet.set((1, 0), "e");
+        editor.edits += 1;
+        editor.last_key = Instant::now() - Duration::from_secs(10);
+        editor.swapped_at = Instant::now() - SWAP_DELAY;
+        assert!(editor.idle().unwrap().is_none());
+        assert_eq!(editor.saved_edits, editor.edits);
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "a\te\n");
+        assert!(!swap_path(&file_path).exists());
+
+        fs::remove_dir_all(Path::new(&file_path).parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn autosave_backoff() {
+        // Read-only sheets fail to be saved.
+        let mut editor = Editor::new();
+        editor.file_path = Some("data.txt".to_owned());
+        editor.readonly = true;
+        editor.autosave_delay = Some(Duration::from_secs(1));
+        editor.edits += 1;
+
+        editor.autosave().unwrap();
+        assert!(editor
+            .message
+            .take()
+            .is_some_and(|m| m.starts_with("Cannot autosave: ")));
+        assert_eq!(
+            editor.autosave_retry.map(|(_, wait)| wait),
+            Some(AUTOSAVE_RETRY)
+        );
+
+        // Nothing is tried before the wait is over.
+        editor.autosave().unwrap();
+        assert_eq!(
+            editor.autosave_retry.map(|(_, wait)| wait),
+            Some(AUTOSAVE_RETRY)
+        );
+
+        // Failing again is not reported again, and the wait doubles up to its limit.
+        let mut waits = vec![];
+        for _ in 0..8 {
+            editor.autosave_retry = editor
+                .autosave_retry
+                .map(|(_, wait)| (Instant::now(), wait));
+            editor.autosave().unwrap();
+            waits.push(editor.autosave_retry.unwrap().1.as_secs());
+        }
+        assert_eq!(waits, [10, 20, 40, 80, 160, 300, 300, 300]);
+        assert_eq!(editor.message, None);
+
+        // Autosaving when idle waits for the retry, even once no key has been pressed for long.
+        editor.last_key = Instant::now() - Duration::from_secs(10);
+        let timeout = editor.idle().unwrap().unwrap();
+        assert!(timeout > Duration::from_secs(200) && timeout <= AUTOSAVE_MAX_RETRY);
+    }
+
+    #[test]
+    fn readonly_kept() {
+        let mut editor = Editor::new();
+        editor.readonly = true;
+        editor.foreign_swap = true;
+        editor.set(["noreadonly"].into_iter());
+        assert!(editor.readonly);
+        assert!(editor.message.take().is_some());
+
+        let mut editor = Editor::new();
+        editor.file_path = Some("missing/data.txt".to_owned());
+        editor.readonly = true;
+        editor.set(["noreadonly"].into_iter());
+        assert!(editor.readonly);
+        assert!(editor.message.take().is_some());
+
+        let mut editor = Editor::new();
+        editor.readonly = true;
+        editor.set(["noreadonly"].into_iter());
+        assert!(!editor.readonly);
+    }
+
+    #[test]
+    fn view_commands_only_view() {
+        let mut editor = Editor::new();
+        let backup = editor.backup;
+        editor.apply_view_command("set readonly autosave=1 autosaveedits=1 tabsize=4");
+        editor.apply_view_command(if backup { "set nobackup" } else { "set backup" });
+        assert!(!editor.readonly);
+        assert_eq!(editor.backup, backup);
+        assert_eq!(editor.autosave_delay, None);
+        assert_eq!(editor.autosave_edits, None);
+        assert_eq!(editor.sheet.tab_size(), 4);
+
+        editor.readonly = true;
+        editor.apply_view_command("set noreadonly");
+        assert!(editor.readonly);
+    }
+
+    #[test]
+    fn quoting() {
+        for s in ["", "a  b", " a ", "\"", "a\\", "\\\"x\""] {
+            assert_eq!(unquote(&quote(s)).as_deref(), Some(s));
+        }
+        assert_eq!(unquote("a"), None);
+        assert_eq!(unquote("\""), None);
+        assert_eq!(unquote("\"a\"b\""), None);
+        assert_eq!(unquote("\"a\\\""), None);
+    }
+
+    #[test]
+    fn filters_keep_their_values() {
+        let mut editor = Editor::new();
+        editor.filter("header A = \"  two  spaces \"");
+        editor.filter("B ~ a  b");
+        editor.filter("C =~ ^\\d+\"$");
+        editor.filter("D >= 2");
+
+        let view = editor.view_commands();
+        let mut restored = Editor::new();
+        for cmd in &view {
+            restored.apply_view_command(cmd);
+        }
+        assert_eq!(restored.view_commands(), view);
+        assert!(restored.filter_header);
+
+        let values: Vec<String> = restored
+            .filters
+            .iter()
+            .map(|filter| match &filter.condition {
+                Condition::Equals(value) | Condition::Contains(value) => value.to_owned(),
+                Condition::Matches(regex) => regex.as_str().to_owned(),
+                Condition::Compare(_, n) => n.to_string(),
+            })
+            .collect();
+        assert_eq!(values, ["  two  spaces ", "a  b", "^\\d+\"$", "2"]);
+    }
+
+    #[test]
+    fn column_widths() {
+        let mut editor = Editor::new();
+        editor.sheet = Sheet::from_str("a\tlonger than a tab\tc\n");
+        assert_eq!(editor.content_width(0), 7);
+        assert_eq!(editor.content_width(1), 23);
+
+        for cmd in ["colwidth A 3", "colwidth B max=10", "colwidth C min=12"] {
+            editor.apply_view_command(cmd);
+        }
+        assert_eq!(editor.content_width(0), 3);
+        assert_eq!(editor.content_width(1), 10);
+        assert_eq!(editor.content_width(2), 12);
+        editor.apply_view_command("colwidth B min=5");
+        assert_eq!(editor.content_width(1), 23);
+        editor.apply_view_command("colwidth A auto");
+        assert_eq!(editor.content_width(0), 7);
+
+        editor.apply_view_command("hide B");
+        assert_eq!(editor.display_width(1), 0);
+        let accum = editor.accum_display_widths();
+        assert_eq!(accum, [0, 8, 8, 21]);
+        assert_eq!(editor.accum_display_width(&accum, 5), 21 + 2 * 8);
+        assert!(!editor.is_hidden(5));
+    }
+
+    #[test]
+    fn frozen_scrolling() {
+        let mut editor = Editor::new();
+        let row = ["x"; 10].join("\t");
+        editor.sheet = Sheet::from_str(&vec![row; 20].join("\n"));
+        editor.apply_view_command("freeze rows=1 cols=1");
+        let size = (40, 6);
+
+        for _ in 0..9 {
+            editor.move_pos_on_screen(1, 0, size);
+        }
+        for _ in 0..19 {
+            editor.move_pos_on_screen(0, 1, size);
+        }
+        assert_eq!(editor.pos, (9, 19));
+
+        // The frozen column and row stay in place, and the cursor in view next to them.
+        let accum = editor.accum_display_widths();
+        assert_eq!(editor.display_x(&accum, 0), Some(0));
+        assert_eq!(editor.display_x(&accum, 1), None);
+        assert_eq!(editor.display_x(&accum, 9), Some(32));
+        let rows = editor.view_rows();
+        assert_eq!(editor.display_y(&rows, 0), Some(0));
+        assert_eq!(editor.display_y(&rows, 1), None);
+        assert_eq!(editor.display_y(&rows, 19), Some(4));
+        assert_eq!(editor.corner, (6, 16));
+
+        // Moving back into the frozen region does not scroll it.
+        for _ in 0..9 {
+            editor.move_pos_on_screen(-1, -1, size);
+        }
+        assert_eq!(editor.pos, (0, 10));
+        assert_eq!(editor.corner.0, 1);
+        assert_eq!(editor.display_x(&editor.accum_display_widths(), 1), Some(8));
+        assert_eq!(editor.display_y(&editor.view_rows(), 10), Some(1));
+    }
+
+    #[test]
+    fn comment_prefix_takes_effect() {
+        let mut editor = Editor::new();
+        editor.sheet = Sheet::from_str("# a\nb\n=A2\n");
+        editor.pos = (0, 2);
+        editor.apply_view_command("set comment=#");
+        assert_eq!(editor.sheet.size(), (1, 2));
+        assert_eq!(editor.pos, (0, 1));
+        // The formula is written again to refer to `b`, which moved up.
+        assert_eq!(editor.sheet.content_at((0, 1)), Some("=A1"));
+        assert_eq!(editor.edits, 1);
+
+        editor.sheet = Sheet::from_str("# a\nb\n");
+        editor.edits = 0;
+        editor.apply_view_command("set comment=#");
+        assert_eq!(editor.sheet.size(), (1, 1));
+        assert_eq!(editor.edits, 0);
+    }
+}
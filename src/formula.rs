@@ -0,0 +1,1269 @@
+//! Formulas in units whose content starts with `=`.
+//!
+//! A formula is an expression of numbers, strings in double quotes, `TRUE` and `FALSE`,
+//! references to units in A1 notation such as `B3` or `$B$3`, ranges such as `A1:C9`, the
+//! operators `+ - * / ^ & = <> < <= > >=`, parentheses, and the functions `SUM`, `AVERAGE`,
+//! `MIN`, `MAX`, `COUNT`, `IF` and `CONCAT`. References to units that were removed are written
+//! as `#REF!`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error, fmt,
+    ops::Range,
+};
+
+use crate::{
+    cell_ref::{CellRange, CellRef},
+    sheet::{Sheet, Unit},
+    value::CellValue,
+};
+
+/// Deepest nesting of parentheses and function calls in a formula.
+const MAX_DEPTH: usize = 64;
+/// Largest range whose units are each indexed by [`Evaluation`]. Larger ones are checked
+/// against every change instead.
+const MAX_INDEXED_AREA: usize = 1024;
+
+/// Checks if given content is a formula.
+pub fn is_formula(content: &str) -> bool {
+    content.starts_with('=')
+}
+
+/// A parsed formula.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    /// Instructions in postfix order, so that neither evaluating nor dropping a long formula
+    /// takes a deep recursion.
+    code: Vec<Instr>,
+}
+
+impl Formula {
+    /// Parses a formula, with or without the leading `=`.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let s = s.strip_prefix('=').unwrap_or(s);
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            index: 0,
+            depth: 0,
+        };
+
+        let code = parser.parse_expr()?;
+        match parser.peek() {
+            None => Ok(Self { code }),
+            Some(token) => Err(ParseError(format!("unexpected {}", token))),
+        }
+    }
+
+    /// Gets the ranges of units the formula refers to, single units being ranges of one.
+    pub fn references(&self) -> Vec<CellRange> {
+        self.code
+            .iter()
+            .filter_map(|instr| match instr {
+                Instr::Ref(pos) => Some(CellRange::new((*pos).into(), (*pos).into())),
+                Instr::Range(start, end) => Some(CellRange::new((*start).into(), (*end).into())),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A row or column removed from a sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Removal {
+    Row(usize),
+    Col(usize),
+}
+
+impl Removal {
+    /// Shifts a reference past the removed row or column. Returns `None` if it referred to it.
+    fn shift(&self, cell: CellRef) -> Option<CellRef> {
+        let mut cell = cell;
+        let (coord, index) = match *self {
+            Removal::Row(index) => (&mut cell.row, index),
+            Removal::Col(index) => (&mut cell.col, index),
+        };
+        if *coord == index {
+            return None;
+        }
+        if *coord > index {
+            *coord -= 1;
+        }
+
+        Some(cell)
+    }
+
+    /// Shifts the corners of a range, which shrinks if the removed row or column was in it.
+    /// Returns `None` if it was all of it.
+    fn shift_range(&self, start: CellRef, end: CellRef) -> Option<(CellRef, CellRef)> {
+        let coord = |cell: &CellRef| match self {
+            Removal::Row(_) => cell.row,
+            Removal::Col(_) => cell.col,
+        };
+        let index = match *self {
+            Removal::Row(index) | Removal::Col(index) => index,
+        };
+        if coord(&start) == coord(&end) {
+            return Some((self.shift(start)?, self.shift(end)?));
+        }
+
+        // The far corner moves back if the removed one was in the range, and both do if it
+        // was before.
+        let far = coord(&start).max(coord(&end));
+        let shift = |cell: CellRef| {
+            if coord(&cell) > index || coord(&cell) == far && far == index {
+                let mut cell = cell;
+                match self {
+                    Removal::Row(_) => cell.row -= 1,
+                    Removal::Col(_) => cell.col -= 1,
+                }
+                cell
+            } else {
+                cell
+            }
+        };
+
+        Some((shift(start), shift(end)))
+    }
+}
+
+/// Rewrites the references of a formula for the row or column of `removal` having been
+/// removed, writing those referring only to it as `#REF!`. The rest of the formula is kept as
+/// written. Returns `None` if nothing changed.
+pub(crate) fn shift_references(content: &str, removal: Removal) -> Option<String> {
+    rewrite_references(
+        content,
+        |cell| removal.shift(cell),
+        |start, end| removal.shift_range(start, end),
+    )
+}
+
+/// Rewrites the references of a formula for a row having been inserted at `index`, moving
+/// those to it and after it down, and growing ranges across it. Returns `None` if nothing
+/// changed.
+pub(crate) fn insert_row_references(content: &str, index: usize) -> Option<String> {
+    let move_cell = |mut cell: CellRef| {
+        if cell.row >= index {
+            cell.row += 1;
+        }
+        cell
+    };
+
+    rewrite_references(
+        content,
+        |cell| Some(move_cell(cell)),
+        |start, end| Some((move_cell(start), move_cell(end))),
+    )
+}
+
+/// Rewrites the references of a formula for the rows in `rows` having been reordered, the row
+/// at `rows.start + i` moving to `new_rows[i]`, so that they refer to the same units. Ranges
+/// within a single row move with it, and other ranges are kept, as their rows may no longer
+/// be next to each other. Returns `None` if nothing changed.
+pub(crate) fn reorder_row_references(
+    content: &str,
+    rows: &Range<usize>,
+    new_rows: &[usize],
+) -> Option<String> {
+    let move_cell = |mut cell: CellRef| {
+        if rows.contains(&cell.row) {
+            cell.row = new_rows[cell.row - rows.start];
+        }
+        cell
+    };
+
+    rewrite_references(
+        content,
+        |cell| Some(move_cell(cell)),
+        |start, end| {
+            if start.row == end.row {
+                Some((move_cell(start), move_cell(end)))
+            } else {
+                Some((start, end))
+            }
+        },
+    )
+}
+
+/// Rewrites the references of a formula with `shift_cell` and `shift_range`, writing those
+/// they return `None` for as `#REF!`. Returns `None` if nothing changed.
+fn rewrite_references(
+    content: &str,
+    shift_cell: impl Fn(CellRef) -> Option<CellRef>,
+    shift_range: impl Fn(CellRef, CellRef) -> Option<(CellRef, CellRef)>,
+) -> Option<String> {
+    fn split_ident(s: &str) -> (&str, &str) {
+        let is_ident = |c: char| c.is_alphanumeric() || c == '$' || c == '_';
+        s.split_at(s.find(|c| !is_ident(c)).unwrap_or(s.len()))
+    }
+    let is_ident_start = |c: char| c.is_alphabetic() || c == '$' || c == '_';
+
+    let mut shifted = String::with_capacity(content.len());
+    let mut changed = false;
+    let mut rest = content;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '"' {
+            // Strings are copied as they are. Two double quotes in one are copied as two
+            // strings.
+            let end = rest[1..].find('"').map_or(rest.len(), |i| i + 2);
+            shifted.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        if !is_ident_start(c) {
+            shifted.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let (ident, after) = split_ident(rest);
+        let start = match ident.parse::<CellRef>() {
+            Ok(start) if !after.trim_start().starts_with('(') => start,
+            _ => {
+                shifted.push_str(ident);
+                rest = after;
+                continue;
+            }
+        };
+
+        let range = after
+            .trim_start()
+            .strip_prefix(':')
+            .map(|s| split_ident(s.trim_start()))
+            .and_then(|(ident, after)| Some((ident.parse::<CellRef>().ok()?, after)));
+        let (replacement, after) = match range {
+            Some((end, after)) => {
+                let replacement = match shift_range(start, end) {
+                    Some(range) if range == (start, end) => None,
+                    Some((start, end)) => Some(format!("{}:{}", start, end)),
+                    None => Some(ErrorKind::Ref.to_string()),
+                };
+                (replacement, after)
+            }
+            None => {
+                let replacement = match shift_cell(start) {
+                    Some(cell) if cell == start => None,
+                    Some(cell) => Some(cell.to_string()),
+                    None => Some(ErrorKind::Ref.to_string()),
+                };
+                (replacement, after)
+            }
+        };
+
+        match replacement {
+            Some(replacement) => {
+                shifted.push_str(&replacement);
+                changed = true;
+            }
+            None => shifted.push_str(&rest[..rest.len() - after.len()]),
+        }
+        rest = after;
+    }
+
+    changed.then_some(shifted)
+}
+
+/// Error when parsing a formula. Contains the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid formula: {}", self.0)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Result of evaluating a formula.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Empty,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(ErrorKind),
+}
+
+impl Value {
+    /// Reads a plain unit as a value. Dates and times are read as texts.
+    fn from_unit(unit: &Unit) -> Self {
+        match unit.value() {
+            CellValue::Bool(b) => Value::Bool(b),
+            value => match value.as_number() {
+                Some(n) => Value::Number(n),
+                None => Value::Text(unit.content().to_owned()),
+            },
+        }
+    }
+
+    fn as_number(&self) -> Result<f64, ErrorKind> {
+        match self {
+            Value::Empty => Ok(0.0),
+            Value::Number(n) => Ok(*n),
+            Value::Bool(b) => Ok(f64::from(u8::from(*b))),
+            Value::Text(s) => s.trim().parse().map_err(|_| ErrorKind::Value),
+            Value::Error(kind) => Err(*kind),
+        }
+    }
+
+    fn as_bool(&self) -> Result<bool, ErrorKind> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            Value::Text(s) if s.eq_ignore_ascii_case("true") => Ok(true),
+            Value::Text(s) if s.eq_ignore_ascii_case("false") => Ok(false),
+            _ => self.as_number().map(|n| n != 0.0),
+        }
+    }
+
+    fn as_text(&self) -> Result<String, ErrorKind> {
+        match self {
+            Value::Error(kind) => Err(*kind),
+            _ => Ok(self.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Empty => Ok(()),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Number(n) => {
+                let s = format!("{:.10}", n);
+                write!(f, "{}", s.trim_end_matches('0').trim_end_matches('.'))
+            }
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Bool(true) => write!(f, "TRUE"),
+            Value::Bool(false) => write!(f, "FALSE"),
+            Value::Error(kind) => write!(f, "{}", kind),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The formula could not be parsed.
+    Parse,
+    /// An unknown function was called.
+    Name,
+    /// A value has the wrong type, or a function got the wrong number of arguments.
+    Value,
+    DivZero,
+    /// The formula refers to itself, directly or not.
+    Cycle,
+    /// The formula refers to a unit that was removed.
+    Ref,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Parse => write!(f, "#ERROR!"),
+            ErrorKind::Name => write!(f, "#NAME?"),
+            ErrorKind::Value => write!(f, "#VALUE!"),
+            ErrorKind::DivZero => write!(f, "#DIV/0!"),
+            ErrorKind::Cycle => write!(f, "#CYCLE!"),
+            ErrorKind::Ref => write!(f, "#REF!"),
+        }
+    }
+}
+
+/// Values of all formulas in a sheet, along with what they depend on.
+#[derive(Debug, Default)]
+pub struct Evaluation {
+    /// Parsed formulas, or `None` for those failing to be parsed.
+    formulas: HashMap<(usize, usize), Option<Formula>>,
+    values: HashMap<(usize, usize), Value>,
+    /// Formulas referring to each unit, directly or through a range of at most
+    /// [`MAX_INDEXED_AREA`] units.
+    dependents: HashMap<(usize, usize), Vec<(usize, usize)>>,
+    /// Larger ranges, and the formulas referring to them.
+    wide_ranges: Vec<(CellRange, (usize, usize))>,
+}
+
+impl Evaluation {
+    /// Evaluates every formula in `sheet`.
+    pub fn new(sheet: &Sheet) -> Self {
+        let mut evaluation = Self::default();
+        for (pos, content) in sheet.cells() {
+            if is_formula(content) {
+                evaluation.insert(pos, content);
+            }
+        }
+        evaluation.evaluate_all(sheet);
+
+        evaluation
+    }
+
+    /// Gets the value of the formula at `pos`. Returns `None` if there is no formula.
+    pub fn value_at(&self, pos: (usize, usize)) -> Option<&Value> {
+        self.values.get(&pos)
+    }
+
+    /// Updates after the unit at `pos` has changed, evaluating again only the formulas
+    /// depending on it.
+    pub fn update(&mut self, sheet: &Sheet, pos: (usize, usize)) {
+        if let Some(Some(formula)) = self.formulas.remove(&pos) {
+            self.unindex(pos, &formula);
+        }
+        if let Some(content) = sheet.content_at(pos).filter(|s| is_formula(s)) {
+            self.insert(pos, content);
+        }
+
+        for dependent in self.dependents(pos) {
+            self.values.remove(&dependent);
+        }
+        self.values.remove(&pos);
+
+        self.evaluate_all(sheet);
+    }
+
+    /// Parses the formula at `pos`, indexing what it refers to.
+    fn insert(&mut self, pos: (usize, usize), content: &str) {
+        let formula = Formula::parse(content).ok();
+        for range in formula.iter().flat_map(Formula::references) {
+            match range_area(&range) {
+                Some(area) if area <= MAX_INDEXED_AREA => {
+                    for cell in range_positions(&range) {
+                        self.dependents.entry(cell).or_default().push(pos);
+                    }
+                }
+                _ => self.wide_ranges.push((range, pos)),
+            }
+        }
+
+        self.formulas.insert(pos, formula);
+    }
+
+    /// Removes what the formula at `pos` refers to from the index.
+    fn unindex(&mut self, pos: (usize, usize), formula: &Formula) {
+        for range in formula.references() {
+            if range_area(&range).is_some_and(|area| area <= MAX_INDEXED_AREA) {
+                for cell in range_positions(&range) {
+                    if let Some(dependents) = self.dependents.get_mut(&cell) {
+                        dependents.retain(|&dependent| dependent != pos);
+                        if dependents.is_empty() {
+                            self.dependents.remove(&cell);
+                        }
+                    }
+                }
+            }
+        }
+        self.wide_ranges.retain(|&(_, dependent)| dependent != pos);
+    }
+
+    /// Gets the formulas depending on the unit at `pos`, directly or not.
+    fn dependents(&self, pos: (usize, usize)) -> HashSet<(usize, usize)> {
+        let mut dependents = HashSet::new();
+        let mut queue = vec![pos];
+
+        while let Some(changed) = queue.pop() {
+            let direct = self.dependents.get(&changed).into_iter().flatten().copied();
+            let wide = self
+                .wide_ranges
+                .iter()
+                .filter(|(range, _)| range.contains(changed))
+                .map(|&(_, dependent)| dependent);
+            for dependent in direct.chain(wide) {
+                if dependents.insert(dependent) {
+                    queue.push(dependent);
+                }
+            }
+        }
+
+        dependents
+    }
+
+    /// Gets the formulas the formula at `pos` refers to, directly or through ranges.
+    fn precedents(&self, sheet: &Sheet, pos: (usize, usize)) -> Vec<(usize, usize)> {
+        let formula = match self.formulas.get(&pos) {
+            Some(Some(formula)) => formula,
+            _ => return vec![],
+        };
+
+        let mut precedents = vec![];
+        for range in formula.references() {
+            // Formulas all lie in the sheet, so only that part of the range is looked through,
+            // unless there are fewer formulas than units in it.
+            let (min, max) = (range.min(), range.max());
+            let max = (
+                max.0.min(sheet.size().0.saturating_sub(1)),
+                max.1.min(sheet.size().1.saturating_sub(1)),
+            );
+            let area = (max.0 + 1)
+                .saturating_sub(min.0)
+                .saturating_mul((max.1 + 1).saturating_sub(min.1));
+
+            if area <= self.formulas.len() {
+                for row in min.1..=max.1 {
+                    for col in min.0..=max.0 {
+                        if self.formulas.contains_key(&(col, row)) {
+                            precedents.push((col, row));
+                        }
+                    }
+                }
+            } else {
+                precedents.extend(self.formulas.keys().filter(|&&p| range.contains(p)));
+            }
+        }
+
+        precedents
+    }
+
+    /// Evaluates the formulas without values.
+    fn evaluate_all(&mut self, sheet: &Sheet) {
+        let positions: Vec<(usize, usize)> = self
+            .formulas
+            .keys()
+            .filter(|pos| !self.values.contains_key(pos))
+            .copied()
+            .collect();
+        for pos in positions {
+            if !self.values.contains_key(&pos) {
+                self.evaluate_from(sheet, pos);
+            }
+        }
+    }
+
+    /// Evaluates the formula at `start` after the formulas it refers to, directly or not, in
+    /// depth-first order. The formulas being visited are kept on a stack of their own rather
+    /// than the call stack, so that a long chain of references cannot overflow it.
+    fn evaluate_from(&mut self, sheet: &Sheet, start: (usize, usize)) {
+        // Each formula being visited, with the formulas it refers to and how many of them
+        // have been visited.
+        let mut stack = vec![(start, self.precedents(sheet, start), 0)];
+        let mut visiting = HashSet::from([start]);
+        let mut cyclic = HashSet::new();
+
+        while let Some((pos, precedents, visited)) = stack.last_mut() {
+            match precedents.get(*visited) {
+                Some(&next) => {
+                    *visited += 1;
+                    if self.values.contains_key(&next) {
+                        continue;
+                    }
+                    if visiting.contains(&next) {
+                        // Every formula from `next` up the stack refers to itself.
+                        let from = stack.iter().position(|entry| entry.0 == next).unwrap();
+                        cyclic.extend(stack[from..].iter().map(|entry| entry.0));
+                        continue;
+                    }
+
+                    visiting.insert(next);
+                    let precedents = self.precedents(sheet, next);
+                    stack.push((next, precedents, 0));
+                }
+                None => {
+                    let pos = *pos;
+                    stack.pop();
+                    visiting.remove(&pos);
+
+                    let value = match self.formulas.get(&pos) {
+                        _ if cyclic.contains(&pos) => Value::Error(ErrorKind::Cycle),
+                        Some(Some(formula)) => self.execute(sheet, &formula.code),
+                        _ => Value::Error(ErrorKind::Parse),
+                    };
+                    self.values.insert(pos, value);
+                }
+            }
+        }
+    }
+
+    /// Gets the value of the unit at `pos`, that of a formula having been evaluated.
+    fn lookup(&self, sheet: &Sheet, pos: (usize, usize)) -> Value {
+        if self.formulas.contains_key(&pos) {
+            return self.values.get(&pos).cloned().unwrap_or(Value::Empty);
+        }
+
+        sheet.unit_at(pos).map_or(Value::Empty, Value::from_unit)
+    }
+
+    /// Runs the code of a formula, whose references to other formulas have been evaluated.
+    fn execute(&self, sheet: &Sheet, code: &[Instr]) -> Value {
+        let mut stack: Vec<Operand> = vec![];
+
+        let mut index = 0;
+        while let Some(instr) = code.get(index) {
+            index += 1;
+            let value = match instr {
+                Instr::Push(value) => value.clone(),
+                Instr::Ref(pos) => self.lookup(sheet, *pos),
+                Instr::Range(start, end) => {
+                    stack.push(Operand::Range(*start, *end));
+                    continue;
+                }
+                Instr::Neg => {
+                    let value = self.pop(sheet, &mut stack);
+                    value
+                        .as_number()
+                        .map_or_else(Value::Error, |n| Value::Number(-n))
+                }
+                Instr::Binary(op) => {
+                    let rhs = self.pop(sheet, &mut stack);
+                    let lhs = self.pop(sheet, &mut stack);
+                    op.apply(&lhs, &rhs).unwrap_or_else(Value::Error)
+                }
+                Instr::Call(name, count) => {
+                    let args = stack.split_off(stack.len().saturating_sub(*count));
+                    let values: Vec<Value> = args
+                        .into_iter()
+                        .flat_map(|arg| self.expand(sheet, arg))
+                        .collect();
+                    call(name, &values).unwrap_or_else(Value::Error)
+                }
+                Instr::Branch { skip, end } => {
+                    match self.pop(sheet, &mut stack).as_bool() {
+                        Ok(true) => (),
+                        Ok(false) => index += skip,
+                        Err(kind) => {
+                            stack.push(Operand::Value(Value::Error(kind)));
+                            index += end;
+                        }
+                    }
+                    continue;
+                }
+                Instr::Jump(skip) => {
+                    index += skip;
+                    continue;
+                }
+            };
+            stack.push(Operand::Value(value));
+        }
+
+        self.pop(sheet, &mut stack)
+    }
+
+    /// Pops an operand as a single value. A range on its own stands for its first unit.
+    fn pop(&self, sheet: &Sheet, stack: &mut Vec<Operand>) -> Value {
+        match stack.pop() {
+            Some(Operand::Value(value)) => value,
+            Some(Operand::Range(start, _)) => self.lookup(sheet, start),
+            None => Value::Empty,
+        }
+    }
+
+    /// Expands an argument of a function into its values, one for each unit of a range.
+    fn expand(&self, sheet: &Sheet, operand: Operand) -> Vec<Value> {
+        match operand {
+            Operand::Value(value) => vec![value],
+            Operand::Range(start, end) => {
+                let end = (
+                    end.0.min(sheet.size().0.saturating_sub(1)),
+                    end.1.min(sheet.size().1.saturating_sub(1)),
+                );
+                (start.1..=end.1)
+                    .flat_map(|row| (start.0..=end.0).map(move |col| (col, row)))
+                    .map(|pos| self.lookup(sheet, pos))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Counts the units in a range. Returns `None` if there are too many to count.
+fn range_area(range: &CellRange) -> Option<usize> {
+    let (min, max) = (range.min(), range.max());
+    (max.0 - min.0)
+        .checked_add(1)?
+        .checked_mul((max.1 - min.1).checked_add(1)?)
+}
+
+fn range_positions(range: &CellRange) -> impl Iterator<Item = (usize, usize)> {
+    let (min, max) = (range.min(), range.max());
+    (min.1..=max.1).flat_map(move |row| (min.0..=max.0).map(move |col| (col, row)))
+}
+
+/// Calls the function of `name` with the values of its arguments, ranges expanded. `IF` is
+/// not called this way, as only one of its branches is evaluated.
+fn call(name: &str, values: &[Value]) -> Result<Value, ErrorKind> {
+    if let Some(Value::Error(kind)) = values.iter().find(|v| matches!(v, Value::Error(_))) {
+        return Err(*kind);
+    }
+    // Only numbers count in aggregates, as texts and empty units in ranges are skipped.
+    let numbers: Vec<f64> = values
+        .iter()
+        .filter_map(|value| match value {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        })
+        .collect();
+
+    match name {
+        "SUM" => Ok(Value::Number(numbers.iter().sum())),
+        "AVERAGE" if numbers.is_empty() => Err(ErrorKind::DivZero),
+        "AVERAGE" => Ok(Value::Number(
+            numbers.iter().sum::<f64>() / numbers.len() as f64,
+        )),
+        "MIN" => Ok(Value::Number(
+            numbers.iter().copied().reduce(f64::min).unwrap_or(0.0),
+        )),
+        "MAX" => Ok(Value::Number(
+            numbers.iter().copied().reduce(f64::max).unwrap_or(0.0),
+        )),
+        "COUNT" => Ok(Value::Number(numbers.len() as f64)),
+        "CONCAT" => values
+            .iter()
+            .map(Value::as_text)
+            .collect::<Result<String, _>>()
+            .map(Value::Text),
+        _ => Err(ErrorKind::Name),
+    }
+}
+
+/// An operand of the code of a formula.
+enum Operand {
+    Value(Value),
+    /// A range, expanded only as an argument of a function.
+    Range((usize, usize), (usize, usize)),
+}
+
+/// An instruction of the code of a formula, run on a stack of operands.
+#[derive(Debug, Clone, PartialEq)]
+enum Instr {
+    Push(Value),
+    Ref((usize, usize)),
+    /// Upper left and lower right corners, both inclusive.
+    Range((usize, usize), (usize, usize)),
+    Neg,
+    Binary(Op),
+    /// Calls a function with the operands on top of the stack, as many as given.
+    Call(String, usize),
+    /// Pops a condition, going on if it holds, or skipping as many instructions as `skip` if
+    /// not. If it is an error, the error is pushed and as many as `end` are skipped.
+    Branch {
+        skip: usize,
+        end: usize,
+    },
+    /// Skips as many instructions as given.
+    Jump(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn apply(&self, lhs: &Value, rhs: &Value) -> Result<Value, ErrorKind> {
+        match self {
+            Op::Add => Ok(Value::Number(lhs.as_number()? + rhs.as_number()?)),
+            Op::Sub => Ok(Value::Number(lhs.as_number()? - rhs.as_number()?)),
+            Op::Mul => Ok(Value::Number(lhs.as_number()? * rhs.as_number()?)),
+            Op::Div => {
+                let divisor = rhs.as_number()?;
+                if divisor == 0.0 {
+                    Err(ErrorKind::DivZero)
+                } else {
+                    Ok(Value::Number(lhs.as_number()? / divisor))
+                }
+            }
+            Op::Pow => Ok(Value::Number(lhs.as_number()?.powf(rhs.as_number()?))),
+            Op::Concat => Ok(Value::Text(lhs.as_text()? + &rhs.as_text()?)),
+            _ => {
+                let ordering = match (lhs, rhs) {
+                    (Value::Error(kind), _) | (_, Value::Error(kind)) => return Err(*kind),
+                    (Value::Number(_) | Value::Empty, Value::Number(_) | Value::Empty) => {
+                        lhs.as_number()?.total_cmp(&rhs.as_number()?)
+                    }
+                    _ => lhs.as_text()?.cmp(&rhs.as_text()?),
+                };
+                Ok(Value::Bool(match self {
+                    Op::Eq => ordering.is_eq(),
+                    Op::Ne => ordering.is_ne(),
+                    Op::Lt => ordering.is_lt(),
+                    Op::Le => ordering.is_le(),
+                    Op::Gt => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    /// A name of a function, a reference, or `TRUE` or `FALSE`.
+    Ident(String),
+    Op(Op),
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+    /// `#REF!`.
+    RefError,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "`{}`", n),
+            Token::Text(s) => write!(f, "{:?}", s),
+            Token::Ident(s) => write!(f, "`{}`", s),
+            Token::Op(op) => write!(f, "operator {:?}", op),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Colon => write!(f, "`:`"),
+            Token::RefError => write!(f, "`{}`", ErrorKind::Ref),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            ' ' | '\t' => continue,
+            '0'..='9' | '.' => {
+                let mut number = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                let n = number
+                    .parse()
+                    .map_err(|_| ParseError(format!("invalid number `{}`", number)))?;
+                Token::Number(n)
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        // Two double quotes stand for one.
+                        Some('"') if chars.next_if_eq(&'"').is_some() => text.push('"'),
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(ParseError("unterminated string".to_owned())),
+                    }
+                }
+                Token::Text(text)
+            }
+            c if c.is_alphabetic() || c == '$' || c == '_' => {
+                let mut ident = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '$' || *c == '_')
+                {
+                    ident.push(c);
+                }
+                Token::Ident(ident.to_uppercase())
+            }
+            '+' => Token::Op(Op::Add),
+            '-' => Token::Op(Op::Sub),
+            '*' => Token::Op(Op::Mul),
+            '/' => Token::Op(Op::Div),
+            '^' => Token::Op(Op::Pow),
+            '&' => Token::Op(Op::Concat),
+            '=' => Token::Op(Op::Eq),
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Le),
+            '<' if chars.next_if_eq(&'>').is_some() => Token::Op(Op::Ne),
+            '<' => Token::Op(Op::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' | ';' => Token::Comma,
+            ':' => Token::Colon,
+            '#' if chars.clone().take(4).eq("REF!".chars()) => {
+                chars.nth(3);
+                Token::RefError
+            }
+            _ => return Err(ParseError(format!("unexpected `{}`", c))),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn parse_reference(s: &str) -> Result<(usize, usize), ParseError> {
+    s.parse::<CellRef>()
+        .map(|cell| cell.pos())
+        .map_err(|err| ParseError(err.to_string()))
+}
+
+/// Recursive descent parser, from the lowest precedence to the highest: comparisons, `&`,
+/// `+ -`, `* /`, `^`, unary `-`. Expressions are parsed into code, in postfix order.
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    /// Nesting of the parentheses and function calls being parsed.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn next_op(&mut self, ops: &[Op]) -> Option<Op> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.index += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(ParseError(format!(
+                "expected {}, found {}",
+                expected, token
+            ))),
+            None => Err(ParseError(format!("expected {}", expected))),
+        }
+    }
+
+    /// Parses what `parse` does one level of nesting deeper, failing past [`MAX_DEPTH`].
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ParseError("too deeply nested".to_owned()));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn parse_binary(
+        &mut self,
+        ops: &[Op],
+        operand: fn(&mut Self) -> Result<Vec<Instr>, ParseError>,
+    ) -> Result<Vec<Instr>, ParseError> {
+        let mut code = operand(self)?;
+        while let Some(op) = self.next_op(ops) {
+            code.extend(operand(self)?);
+            code.push(Instr::Binary(op));
+        }
+
+        Ok(code)
+    }
+
+    fn parse_expr(&mut self) -> Result<Vec<Instr>, ParseError> {
+        self.parse_binary(
+            &[Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge],
+            Self::parse_concat,
+        )
+    }
+
+    fn parse_concat(&mut self) -> Result<Vec<Instr>, ParseError> {
+        self.parse_binary(&[Op::Concat], Self::parse_additive)
+    }
+
+    fn parse_additive(&mut self) -> Result<Vec<Instr>, ParseError> {
+        self.parse_binary(&[Op::Add, Op::Sub], Self::parse_term)
+    }
+
+    fn parse_term(&mut self) -> Result<Vec<Instr>, ParseError> {
+        self.parse_binary(&[Op::Mul, Op::Div], Self::parse_power)
+    }
+
+    fn parse_power(&mut self) -> Result<Vec<Instr>, ParseError> {
+        self.parse_binary(&[Op::Pow], Self::parse_unary)
+    }
+
+    /// Parses an operand after any number of signs, which are counted rather than nested.
+    fn parse_unary(&mut self) -> Result<Vec<Instr>, ParseError> {
+        let (mut negated, mut negative) = (false, false);
+        while let Some(op) = self.next_op(&[Op::Sub, Op::Add]) {
+            if op == Op::Sub {
+                negated = true;
+                negative = !negative;
+            }
+        }
+
+        let mut code = self.parse_primary()?;
+        // Negating twice still reads the operand as a number.
+        if negated {
+            code.push(Instr::Neg);
+            if !negative {
+                code.push(Instr::Neg);
+            }
+        }
+
+        Ok(code)
+    }
+
+    fn parse_primary(&mut self) -> Result<Vec<Instr>, ParseError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(vec![Instr::Push(Value::Number(n))]),
+            Some(Token::Text(s)) => Ok(vec![Instr::Push(Value::Text(s))]),
+            Some(Token::RefError) => Ok(vec![Instr::Push(Value::Error(ErrorKind::Ref))]),
+            Some(Token::LParen) => self.nested(|parser| {
+                let code = parser.parse_expr()?;
+                parser.expect(Token::RParen)?;
+                Ok(code)
+            }),
+            Some(Token::Ident(ident)) if self.peek() == Some(&Token::LParen) => {
+                self.index += 1;
+                let args = self.nested(|parser| {
+                    let mut args = vec![];
+                    if parser.peek() != Some(&Token::RParen) {
+                        args.push(parser.parse_expr()?);
+                        while parser.peek() == Some(&Token::Comma) {
+                            parser.index += 1;
+                            args.push(parser.parse_expr()?);
+                        }
+                    }
+                    parser.expect(Token::RParen)?;
+                    Ok(args)
+                })?;
+                Ok(compile_call(ident, args))
+            }
+            Some(Token::Ident(ident)) if ident == "TRUE" => {
+                Ok(vec![Instr::Push(Value::Bool(true))])
+            }
+            Some(Token::Ident(ident)) if ident == "FALSE" => {
+                Ok(vec![Instr::Push(Value::Bool(false))])
+            }
+            Some(Token::Ident(ident)) => {
+                let start = parse_reference(&ident)?;
+                if self.peek() != Some(&Token::Colon) {
+                    return Ok(vec![Instr::Ref(start)]);
+                }
+
+                self.index += 1;
+                let end = match self.next() {
+                    Some(Token::Ident(ident)) => parse_reference(&ident)?,
+                    _ => return Err(ParseError("expected a reference after `:`".to_owned())),
+                };
+                Ok(vec![Instr::Range(
+                    (start.0.min(end.0), start.1.min(end.1)),
+                    (start.0.max(end.0), start.1.max(end.1)),
+                )])
+            }
+            Some(token) => Err(ParseError(format!("unexpected {}", token))),
+            None => Err(ParseError("unexpected end".to_owned())),
+        }
+    }
+}
+
+/// Builds the code calling the function of `name` with the code of its arguments. Only one
+/// branch of `IF` is run, after its condition.
+fn compile_call(name: String, args: Vec<Vec<Instr>>) -> Vec<Instr> {
+    if name != "IF" {
+        let count = args.len();
+        let mut code: Vec<Instr> = args.into_iter().flatten().collect();
+        code.push(Instr::Call(name, count));
+        return code;
+    }
+
+    let mut args = args.into_iter();
+    match (args.next(), args.next(), args.next(), args.next()) {
+        (Some(condition), Some(then), otherwise, None) => {
+            let otherwise = otherwise.unwrap_or_else(|| vec![Instr::Push(Value::Bool(false))]);
+            let mut code = condition;
+            code.push(Instr::Branch {
+                skip: then.len() + 1,
+                end: then.len() + 1 + otherwise.len(),
+            });
+            code.extend(then);
+            code.push(Instr::Jump(otherwise.len()));
+            code.extend(otherwise);
+            code
+        }
+        _ => vec![Instr::Push(Value::Error(ErrorKind::Value))],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(buf: &str, pos: (usize, usize)) -> Value {
+        let sheet = Sheet::from_str(buf);
+        Evaluation::new(&sheet).value_at(pos).unwrap().clone()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("=1+2*3", (0, 0)), Value::Number(7.0));
+        assert_eq!(eval("=(1+2)*3", (0, 0)), Value::Number(9.0));
+        assert_eq!(eval("=2^3^2", (0, 0)), Value::Number(64.0));
+        assert_eq!(eval("=-2^2", (0, 0)), Value::Number(4.0));
+        assert_eq!(eval("=1+1=2", (0, 0)), Value::Bool(true));
+        assert_eq!(eval("=1&2+3", (0, 0)), Value::Text("15".to_owned()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Formula::parse("=1+").is_err());
+        assert!(Formula::parse("=(1").is_err());
+        assert!(Formula::parse("=1 2").is_err());
+        assert!(Formula::parse("=A1:").is_err());
+        assert!(Formula::parse("=#N/A").is_err());
+        assert_eq!(eval("=1+", (0, 0)), Value::Error(ErrorKind::Parse));
+    }
+
+    #[test]
+    fn many_signs() {
+        let formula = format!("={}1", "-".repeat(100_000));
+        assert_eq!(eval(&formula, (0, 0)), Value::Number(1.0));
+        let formula = format!("={}1", "-".repeat(99_999));
+        assert_eq!(eval(&formula, (0, 0)), Value::Number(-1.0));
+        assert_eq!(eval("=--\"2\"", (0, 0)), Value::Number(2.0));
+    }
+
+    #[test]
+    fn nesting_is_capped() {
+        let nested = |depth| format!("={}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(Formula::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Formula::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Formula::parse(&nested(100_000)).is_err());
+
+        let calls = format!(
+            "={}1{}",
+            "SUM(".repeat(MAX_DEPTH + 1),
+            ")".repeat(MAX_DEPTH + 1)
+        );
+        assert!(Formula::parse(&calls).is_err());
+    }
+
+    #[test]
+    fn functions() {
+        let buf = "1\t2\ta\n3\t\t=SUM(A1:C1,A2)\n=AVERAGE(A1:B2)\t=COUNT(A1:C2)\t=CONCAT(C1,A1)\n";
+        assert_eq!(eval(buf, (2, 1)), Value::Number(6.0));
+        assert_eq!(eval(buf, (0, 2)), Value::Number(2.0));
+        assert_eq!(eval(buf, (1, 2)), Value::Number(4.0));
+        assert_eq!(eval(buf, (2, 2)), Value::Text("a1".to_owned()));
+        assert_eq!(eval("=NOPE(1)", (0, 0)), Value::Error(ErrorKind::Name));
+        assert_eq!(
+            eval("=AVERAGE(B1)", (0, 0)),
+            Value::Error(ErrorKind::DivZero)
+        );
+    }
+
+    #[test]
+    fn if_runs_one_branch() {
+        assert_eq!(
+            eval("=IF(1<2,\"y\",1/0)", (0, 0)),
+            Value::Text("y".to_owned())
+        );
+        assert_eq!(
+            eval("=IF(1>2,1/0,\"n\")", (0, 0)),
+            Value::Text("n".to_owned())
+        );
+        assert_eq!(eval("=IF(1>2,1)", (0, 0)), Value::Bool(false));
+        assert_eq!(
+            eval("=IF(1/0,1,2)", (0, 0)),
+            Value::Error(ErrorKind::DivZero)
+        );
+        assert_eq!(eval("=IF(1)", (0, 0)), Value::Error(ErrorKind::Value));
+        assert_eq!(
+            eval("=IF(TRUE,IF(FALSE,1,2),3)+1", (0, 0)),
+            Value::Number(3.0)
+        );
+    }
+
+    #[test]
+    fn long_chain() {
+        let mut buf = "1\n".to_owned();
+        for row in 1..20_000 {
+            buf.push_str(&format!("=A{}+1\n", row));
+        }
+        assert_eq!(eval(&buf, (0, 19_999)), Value::Number(20_000.0));
+    }
+
+    #[test]
+    fn cycles() {
+        assert_eq!(eval("=A1", (0, 0)), Value::Error(ErrorKind::Cycle));
+
+        let buf = "=B1\t=A1\t=A1+1\t=SUM(A2)\n5\n";
+        assert_eq!(eval(buf, (0, 0)), Value::Error(ErrorKind::Cycle));
+        assert_eq!(eval(buf, (1, 0)), Value::Error(ErrorKind::Cycle));
+        assert_eq!(eval(buf, (2, 0)), Value::Error(ErrorKind::Cycle));
+        assert_eq!(eval(buf, (3, 0)), Value::Number(5.0));
+
+        assert_eq!(
+            eval("=SUM(A1:B1)\t1\n", (0, 0)),
+            Value::Error(ErrorKind::Cycle)
+        );
+    }
+
+    #[test]
+    fn update_dependents() {
+        let mut sheet = Sheet::from_str("1\t=A1*2\t=B1+1\t=SUM(A1:A5000)\n");
+        let mut evaluation = Evaluation::new(&sheet);
+        assert_eq!(
+            evaluation.dependents((0, 0)),
+            HashSet::from([(1, 0), (2, 0), (3, 0)])
+        );
+        assert_eq!(evaluation.dependents((1, 0)), HashSet::from([(2, 0)]));
+
+        sheet.edit((0, 0), "5");
+        evaluation.update(&sheet, (0, 0));
+        assert_eq!(evaluation.value_at((2, 0)), Some(&Value::Number(11.0)));
+        assert_eq!(evaluation.value_at((3, 0)), Some(&Value::Number(5.0)));
+
+        sheet.edit((1, 0), "7");
+        evaluation.update(&sheet, (1, 0));
+        assert_eq!(evaluation.value_at((1, 0)), None);
+        assert_eq!(evaluation.value_at((2, 0)), Some(&Value::Number(8.0)));
+        assert_eq!(evaluation.dependents((0, 0)), HashSet::from([(3, 0)]));
+    }
+
+    #[test]
+    fn shift_rows() {
+        let shift = |s| shift_references(s, Removal::Row(1));
+        assert_eq!(shift("=A1+A3*$B$4"), Some("=A1+A2*$B$3".to_owned()));
+        assert_eq!(shift("=A2+1"), Some("=#REF!+1".to_owned()));
+        assert_eq!(shift("=SUM(A1:B3)"), Some("=SUM(A1:B2)".to_owned()));
+        assert_eq!(shift("=SUM(A2:A3)"), Some("=SUM(A2:A2)".to_owned()));
+        assert_eq!(shift("=SUM(A3:A5)"), Some("=SUM(A2:A4)".to_owned()));
+        assert_eq!(shift("=SUM(A2:B2)"), Some("=SUM(#REF!)".to_owned()));
+        assert_eq!(shift("=SUM(A1 : A1)"), None);
+        assert_eq!(shift("=\"A3\"&a3"), Some("=\"A3\"&A2".to_owned()));
+        assert_eq!(shift("=LOG10(2)"), None);
+
+        assert_eq!(eval("=#REF!+1", (0, 0)), Value::Error(ErrorKind::Ref));
+    }
+
+    #[test]
+    fn shift_cols() {
+        let shift = |s| shift_references(s, Removal::Col(1));
+        assert_eq!(shift("=A1+C1"), Some("=A1+B1".to_owned()));
+        assert_eq!(shift("=B7"), Some("=#REF!".to_owned()));
+        assert_eq!(shift("=SUM(A1:C2)"), Some("=SUM(A1:B2)".to_owned()));
+    }
+
+    #[test]
+    fn sheet_shifts_formulas() {
+        let mut sheet = Sheet::from_str("1\tx\t2\n\n3\t\t=A3+C1\n");
+        sheet.set_collapse_empty(true);
+        sheet.edit((0, 1), "");
+        assert_eq!(sheet.content_at((2, 1)), Some("=A2+C1"));
+        sheet.edit((1, 0), "");
+        assert_eq!(sheet.content_at((1, 1)), Some("=A2+B1"));
+
+        let evaluation = Evaluation::new(&sheet);
+        assert_eq!(evaluation.value_at((1, 1)), Some(&Value::Number(5.0)));
+    }
+}
@@ -1,4 +1,9 @@
+pub mod cell_ref;
+pub mod cli;
 pub mod editor;
+pub mod formula;
+pub mod record;
 pub mod sheet;
+pub mod value;
 
 mod util;
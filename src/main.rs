@@ -1,17 +1,59 @@
 use std::env;
+use std::io::{self, IsTerminal};
 use std::process;
 
+use tab_o_txt::cli::{self, Command, EditOptions, USAGE};
 use tab_o_txt::editor::Editor;
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    let mut session = Editor::from(&args).unwrap_or_else(|err| {
-        println!("Error when starting editor: {}", err);
-        process::exit(1);
+    let command = Command::parse(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("Error: {}\n\n{}", err, USAGE);
+        process::exit(2);
     });
 
-    session.run().unwrap_or_else(|err| {
-        println!("Rumtime error: {}", err);
+    let result = match command {
+        Command::Edit(options) => edit(&options),
+        Command::Get { file_path, cell } => cli::get(&file_path, cell, &mut io::stdout().lock()),
+        Command::Set {
+            file_path,
+            cell,
+            content,
+        } => cli::set(&file_path, cell, &content),
+        Command::Fmt { file_path } => cli::format(&file_path),
+        Command::Convert {
+            input,
+            output,
+            from,
+            to,
+        } => cli::convert(&input, &output, from, to),
+        Command::Select {
+            input,
+            cols,
+            from,
+            to,
+        } => cli::select(&input, &cols, from, to, &mut io::stdout().lock()),
+        Command::Check { file_path, layout } => {
+            cli::check(&file_path, layout, &mut io::stdout().lock())
+        }
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    };
+
+    result.unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
         process::exit(1);
     });
 }
+
+fn edit(options: &EditOptions) -> io::Result<()> {
+    let mut session = Editor::from(options)?;
+
+    // Without a terminal to edit in, the sheet is passed on, such as in a pipeline.
+    if !io::stdout().is_terminal() {
+        return session.write_to(&mut io::stdout().lock());
+    }
+
+    session.run()
+}
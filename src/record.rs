@@ -0,0 +1,346 @@
+//! Reading rows of a sheet into typed records, and writing them back.
+//!
+//! The first row of a sheet is taken as the names of fields. Types implement [`FromRecord`] to
+//! be read with [`Sheet::records`](crate::sheet::Sheet::records), and [`ToRecord`] to be written
+//! with [`Sheet::from_records`](crate::sheet::Sheet::from_records). For plain structs, both can
+//! be implemented with [`impl_record!`](crate::impl_record).
+
+use std::{error, fmt};
+
+use crate::sheet::Sheet;
+
+/// A type that can be read from a row of a sheet.
+pub trait FromRecord: Sized {
+    fn from_record(record: &Record) -> Result<Self, RecordError>;
+}
+
+/// A type that can be written as a row of a sheet.
+pub trait ToRecord {
+    /// Names of fields, written as the first row.
+    fn fields() -> Vec<&'static str>;
+
+    /// Contents of fields, in the order of [`ToRecord::fields`].
+    fn to_record(&self) -> Vec<String>;
+}
+
+/// A value that can be parsed from the content of a unit. `None` stands for an empty unit.
+pub trait FromCell: Sized {
+    fn from_cell(content: Option<&str>) -> Result<Self, String>;
+}
+
+/// A value that can be written as the content of a unit. An empty string leaves it empty.
+pub trait ToCell {
+    fn to_cell(&self) -> String;
+}
+
+/// A row of a sheet, whose units are looked up by the names in the first row.
+pub struct Record<'a> {
+    sheet: &'a Sheet,
+    /// Names of fields and the columns they are in.
+    headers: &'a [(usize, &'a str)],
+    row: usize,
+}
+
+impl<'a> Record<'a> {
+    pub(crate) fn new(sheet: &'a Sheet, headers: &'a [(usize, &'a str)], row: usize) -> Self {
+        Self {
+            sheet,
+            headers,
+            row,
+        }
+    }
+
+    /// Index of the row. Zero-indexed.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Parses the unit under the field of `name`. Fails if no column is named `name`, even if
+    /// the field is optional; an empty unit in the column is read as `None`.
+    pub fn get<T: FromCell>(&self, name: &str) -> Result<T, RecordError> {
+        let error = |col, kind| RecordError {
+            field: name.to_owned(),
+            col,
+            row: self.row,
+            kind,
+        };
+
+        let col = self
+            .headers
+            .iter()
+            .find(|header| header.1 == name)
+            .map(|header| header.0)
+            .ok_or_else(|| error(None, RecordErrorKind::MissingField))?;
+        let content = self.sheet.content_at((col, self.row));
+
+        T::from_cell(content).map_err(|message| error(Some(col), RecordErrorKind::Invalid(message)))
+    }
+}
+
+/// Error when reading a record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordError {
+    field: String,
+    col: Option<usize>,
+    row: usize,
+    kind: RecordErrorKind,
+}
+
+impl RecordError {
+    /// Name of the field that failed to be read.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Position of the unit that failed to be read. Zero-indexed. Represented in `(col, row)`.
+    /// Returns `None` if there is no column for the field.
+    pub fn pos(&self) -> Option<(usize, usize)> {
+        self.col.map(|col| (col, self.row))
+    }
+
+    /// Index of the row that failed to be read. Zero-indexed.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn kind(&self) -> &RecordErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.kind, self.col) {
+            (RecordErrorKind::Invalid(message), Some(col)) => write!(
+                f,
+                "row {}, column {} (`{}`): {}",
+                self.row + 1,
+                col + 1,
+                self.field,
+                message
+            ),
+            _ => write!(f, "row {}: missing field `{}`", self.row + 1, self.field),
+        }
+    }
+}
+
+impl error::Error for RecordError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordErrorKind {
+    /// No column in the first row is named after the field.
+    MissingField,
+    /// The unit could not be parsed. Contains the reason.
+    Invalid(String),
+}
+
+impl FromCell for String {
+    fn from_cell(content: Option<&str>) -> Result<Self, String> {
+        Ok(content.unwrap_or_default().to_owned())
+    }
+}
+
+impl FromCell for bool {
+    fn from_cell(content: Option<&str>) -> Result<Self, String> {
+        match content.map(|s| s.trim().to_lowercase()).as_deref() {
+            Some("true" | "yes" | "1") => Ok(true),
+            Some("false" | "no" | "0") => Ok(false),
+            Some(s) => Err(format!("invalid boolean {:?}", s)),
+            None => Err("missing value".to_owned()),
+        }
+    }
+}
+
+impl<T: FromCell> FromCell for Option<T> {
+    fn from_cell(content: Option<&str>) -> Result<Self, String> {
+        match content {
+            Some(s) if !s.trim().is_empty() => T::from_cell(Some(s)).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+macro_rules! impl_cell_for_parsed {
+    ($($ty:ty)*) => {
+        $(
+            impl FromCell for $ty {
+                fn from_cell(content: Option<&str>) -> Result<Self, String> {
+                    let s = content.ok_or_else(|| "missing value".to_owned())?;
+                    s.trim()
+                        .parse()
+                        .map_err(|err| format!("invalid value {:?}: {}", s, err))
+                }
+            }
+
+            impl ToCell for $ty {
+                fn to_cell(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_cell_for_parsed! { i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64 char }
+
+impl ToCell for String {
+    fn to_cell(&self) -> String {
+        self.clone()
+    }
+}
+
+impl ToCell for &str {
+    fn to_cell(&self) -> String {
+        (*self).to_owned()
+    }
+}
+
+impl ToCell for bool {
+    fn to_cell(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl<T: ToCell> ToCell for Option<T> {
+    fn to_cell(&self) -> String {
+        self.as_ref().map(ToCell::to_cell).unwrap_or_default()
+    }
+}
+
+/// Implements [`FromRecord`] and [`ToRecord`] for a struct, using the names of its fields as
+/// the names in the first row.
+///
+/// ```
+/// use tab_o_txt::impl_record;
+///
+/// struct Item {
+///     name: String,
+///     price: f64,
+///     qty: Option<u32>,
+/// }
+///
+/// impl_record!(Item { name, price, qty });
+/// ```
+#[macro_export]
+macro_rules! impl_record {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::record::FromRecord for $ty {
+            fn from_record(
+                record: &$crate::record::Record,
+            ) -> ::std::result::Result<Self, $crate::record::RecordError> {
+                ::std::result::Result::Ok(Self {
+                    $($field: record.get(stringify!($field))?,)*
+                })
+            }
+        }
+
+        impl $crate::record::ToRecord for $ty {
+            fn fields() -> ::std::vec::Vec<&'static str> {
+                ::std::vec![$(stringify!($field)),*]
+            }
+
+            fn to_record(&self) -> ::std::vec::Vec<::std::string::String> {
+                ::std::vec![$($crate::record::ToCell::to_cell(&self.$field)),*]
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Item {
+        name: String,
+        price: f64,
+        stock: Option<u32>,
+        sold: bool,
+    }
+
+    impl_record!(Item {
+        name,
+        price,
+        stock,
+        sold
+    });
+
+    #[test]
+    fn reads_records() {
+        let sheet =
+            Sheet::from_str("name\tprice\tstock\tsold\napple\t1.5\t3\tyes\n\npear\t2\t\tfalse\n");
+        let items: Vec<Item> = sheet.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            items,
+            [
+                Item {
+                    name: "apple".to_owned(),
+                    price: 1.5,
+                    stock: Some(3),
+                    sold: true,
+                },
+                Item {
+                    name: "pear".to_owned(),
+                    price: 2.0,
+                    stock: None,
+                    sold: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_units() {
+        let sheet = Sheet::from_str("name\tprice\tstock\tsold\napple\tcheap\t3\tno\n");
+        let err = sheet.records::<Item>().next().unwrap().unwrap_err();
+        assert_eq!(err.field(), "price");
+        assert_eq!(err.pos(), Some((1, 1)));
+        assert!(matches!(err.kind(), RecordErrorKind::Invalid(_)));
+        assert!(err.to_string().starts_with("row 2, column 2 (`price`): "));
+
+        let sheet = Sheet::from_str("name\tprice\tstock\tsold\napple\t1\t3\tmaybe\n");
+        let err = sheet.records::<Item>().next().unwrap().unwrap_err();
+        assert_eq!(err.pos(), Some((3, 1)));
+    }
+
+    #[test]
+    fn missing_columns() {
+        // Even fields read as empty strings or `None` need their columns.
+        for buf in [
+            "nme\tprice\tstock\tsold\napple\t1\t3\tno\n",
+            "name\tprice\tsold\napple\t1\tno\n",
+        ] {
+            let err = Sheet::from_str(buf)
+                .records::<Item>()
+                .next()
+                .unwrap()
+                .unwrap_err();
+            assert_eq!(err.kind(), &RecordErrorKind::MissingField);
+            assert_eq!(err.pos(), None);
+            assert_eq!(err.row(), 1);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let items = [
+            Item {
+                name: "apple pie".to_owned(),
+                price: 1.25,
+                stock: None,
+                sold: true,
+            },
+            Item {
+                name: "pear".to_owned(),
+                price: -3.0,
+                stock: Some(0),
+                sold: false,
+            },
+        ];
+        let sheet = Sheet::from_records(&items);
+        assert_eq!(sheet.content_at((0, 0)), Some("name"));
+
+        let read: Vec<Item> = sheet.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(read, items);
+    }
+}
@@ -1,11 +1,37 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
+    fmt,
     fs::File,
-    io::{self, Read},
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+    str::FromStr,
 };
 
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+use crate::formula::{insert_row_references, is_formula, shift_references, Removal};
+use crate::record::{FromRecord, Record, RecordError, ToRecord};
+use crate::util::write_atomically;
+use crate::value::{CellType, CellValue};
+
+mod convert;
+mod filter;
+mod iter;
+mod lint;
+mod merge;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod sort;
+mod stats;
+
+pub use convert::Format;
+pub use filter::{Comparison, Condition, Filter};
+pub use iter::{Cells, RangeView};
+pub use lint::{Issue, IssueKind};
+pub use sort::{Collation, SortKey, SortOrder};
+pub use stats::{Stats, Summary};
+
+#[derive(Clone)]
 pub struct Sheet {
     units: HashMap<(usize, usize), Unit>,
     /// Size of the sheet. Represented in `(col, row)`.
@@ -13,9 +39,129 @@ pub struct Sheet {
     tab_size: usize,
     widths: Vec<usize>,
     accum_widths: Vec<usize>,
+    layout: Layout,
+    /// Whether clearing units removes the rows and columns they leave empty.
+    collapse_empty: bool,
+    /// Declared types of columns. Types of units in other columns are inferred.
+    column_types: HashMap<usize, CellType>,
+    /// Lines starting with this are read as comments rather than rows, if any.
+    comment_prefix: Option<String>,
+    comments: Vec<Comment>,
+}
+
+/// A line kept verbatim, apart from the units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// The row the line comes before, or the count of rows if it comes after all of them.
+    pub row: usize,
+    pub text: String,
+}
+
+/// How a sheet is read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// The layout to read in, or `None` to detect it.
+    pub layout: Option<Layout>,
+    /// How wide tabs are assumed to be.
+    pub tab_size: usize,
+    /// Lines starting with this are kept as comments, such as `# source: ...` for `#`.
+    pub comment_prefix: Option<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            layout: None,
+            tab_size: DEFAULT_TAB_SIZE,
+            comment_prefix: None,
+        }
+    }
 }
 
 const DEFAULT_TAB_SIZE: usize = 8;
+/// Minimum number of spaces between two columns in [`Layout::Spaces`].
+const SPACES_GAP: usize = 2;
+
+/// How a sheet is laid out on disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Layout {
+    /// Columns are aligned by padding with tabs, assuming the tab size of the sheet, 8 by
+    /// default.
+    #[default]
+    Aligned,
+    /// Columns are separated by exactly one tab. Alignment is only computed at display time.
+    Elastic,
+    /// Columns are padded with spaces to fixed widths, at least two spaces apart.
+    Spaces,
+}
+
+impl Layout {
+    /// Guesses the layout of given string slice.
+    ///
+    /// Text containing tabs is considered [`Layout::Aligned`] if every unit fits in its column
+    /// and it reads back the same after being re-aligned, and [`Layout::Elastic`] otherwise.
+    /// Text without tabs is considered [`Layout::Spaces`] if any line has two spaces in a row
+    /// between its units, and [`Layout::Aligned`] otherwise.
+    ///
+    /// The guess changes how units are told apart: a hand-edited tab file that is slightly out
+    /// of line is read with one unit per tab, and `a  b` is read as two units. Give the layout
+    /// explicitly, as with [`Sheet::from_str_with_layout`], where that matters.
+    pub fn detect(buf: &str) -> Self {
+        Self::detect_with_tab_size(buf, DEFAULT_TAB_SIZE)
+    }
+
+    fn detect_with_tab_size(buf: &str, tab_size: usize) -> Self {
+        if !buf.contains('\t') {
+            if buf.lines().any(|line| line.trim().contains("  ")) {
+                return Layout::Spaces;
+            }
+            return Layout::Aligned;
+        }
+
+        let sheet = Sheet::parse_aligned(buf, tab_size);
+        let is_fitting = sheet.units.iter().all(|(pos, unit)| {
+            Sheet::measure_width(&unit.content, sheet.tab_size) <= sheet.widths[pos.0]
+        });
+        let is_aligned = is_fitting
+            && (0..sheet.size.1)
+                .zip(buf.lines())
+                .all(|(row, line)| sheet.format_row(row, Layout::Aligned) == line.trim_end());
+
+        if is_aligned {
+            Layout::Aligned
+        } else {
+            Layout::Elastic
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aligned" => Ok(Layout::Aligned),
+            "elastic" => Ok(Layout::Elastic),
+            "spaces" => Ok(Layout::Spaces),
+            _ => Err(format!("unknown layout: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layout::Aligned => write!(f, "aligned"),
+            Layout::Elastic => write!(f, "elastic"),
+            Layout::Spaces => write!(f, "spaces"),
+        }
+    }
+}
 
 impl Sheet {
     pub fn new() -> Self {
@@ -25,16 +171,17 @@ impl Sheet {
             tab_size: DEFAULT_TAB_SIZE,
             widths: vec![0],
             accum_widths: vec![0, 1],
+            layout: Layout::default(),
+            collapse_empty: false,
+            column_types: HashMap::new(),
+            comment_prefix: None,
+            comments: vec![],
         }
     }
 
     pub fn from_file(path: &str) -> io::Result<Self> {
         let mut buf = String::new();
-        File::options()
-            .read(true)
-            .write(true)
-            .open(path)?
-            .read_to_string(&mut buf)?;
+        File::open(path)?.read_to_string(&mut buf)?;
 
         Ok(Self::from_str(&buf))
     }
@@ -43,6 +190,81 @@ impl Sheet {
         self.tab_size
     }
 
+    /// Gets the lines kept as comments, in order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    pub fn comment_prefix(&self) -> Option<&str> {
+        self.comment_prefix.as_deref()
+    }
+
+    /// Sets the prefix of comment lines, or lets every line be a row with `None`. Rows whose
+    /// lines start with the prefix become comments, and comments that do not become rows, as
+    /// if the sheet were read again, but without changing how the other rows are written.
+    /// Formulas keep referring to the same units.
+    pub fn set_comment_prefix(&mut self, prefix: Option<&str>) {
+        self.comment_prefix = prefix.map(str::to_owned);
+        let is_comment = |line: &str| prefix.is_some_and(|prefix| line.starts_with(prefix));
+
+        // Comments that are no longer, last first so that earlier ones keep their places.
+        for index in (0..self.comments.len()).rev() {
+            if !is_comment(&self.comments[index].text) {
+                let comment = self.comments.remove(index);
+                self.insert_row(index, comment.row, &comment.text);
+            }
+        }
+
+        let space_widths = match self.layout {
+            Layout::Spaces => self.get_space_widths(),
+            _ => vec![],
+        };
+        for row in (0..self.size.1).rev() {
+            let line = match self.layout {
+                Layout::Spaces => self.format_row_spaces(row, &space_widths),
+                layout => self.format_row(row, layout),
+            };
+            if is_comment(&line) {
+                // The comment goes after those before the row, and before those after it.
+                let index = self.comments.partition_point(|comment| comment.row <= row);
+                self.remove_row(row);
+                self.comments.insert(index, Comment { row, text: line });
+            }
+        }
+    }
+
+    /// Sets how wide tabs are assumed to be, measuring the columns again.
+    pub fn set_tab_size(&mut self, tab_size: usize) {
+        self.tab_size = tab_size.max(1);
+        self.realign();
+    }
+
+    /// Measures the columns again, as wide as their widest units. Padding read along with the
+    /// units, such as columns wider than needed, is given up.
+    pub fn realign(&mut self) {
+        self.widths = self.get_col_widths();
+        self.update_accum_widths();
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Sets the layout used when the sheet is written.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    pub fn collapse_empty(&self) -> bool {
+        self.collapse_empty
+    }
+
+    /// Sets whether clearing units removes the rows and columns they leave empty. Off by
+    /// default. [`Sheet::edit`] always does.
+    pub fn set_collapse_empty(&mut self, collapse_empty: bool) {
+        self.collapse_empty = collapse_empty;
+    }
+
     pub fn size(&self) -> (usize, usize) {
         self.size
     }
@@ -55,8 +277,41 @@ impl Sheet {
         self.accum_widths.get(index)
     }
 
-    pub fn content_at(&self, pos: (usize, usize)) -> Option<&str> {
-        self.units.get(&pos).map(|u| u.content.as_str())
+    /// Gets the unit at `pos`, given as a [`CellRef`](crate::cell_ref::CellRef) or in
+    /// `(col, row)`.
+    pub fn unit_at(&self, pos: impl Into<(usize, usize)>) -> Option<&Unit> {
+        self.units.get(&pos.into())
+    }
+
+    /// Gets the typed value at `pos`. See [`Unit::value`].
+    pub fn value_at(&self, pos: impl Into<(usize, usize)>) -> Option<CellValue> {
+        self.unit_at(pos).map(Unit::value)
+    }
+
+    /// Gets the declared type of the column of `index`, if any.
+    pub fn column_type(&self, index: usize) -> Option<CellType> {
+        self.column_types.get(&index).copied()
+    }
+
+    /// Declares the type of the units in the column of `index`, including those set later, or
+    /// lets their types be inferred from their contents with `None`.
+    pub fn set_column_type(&mut self, index: usize, ty: Option<CellType>) {
+        match ty {
+            Some(ty) => self.column_types.insert(index, ty),
+            None => self.column_types.remove(&index),
+        };
+
+        for (pos, unit) in self.units.iter_mut() {
+            if pos.0 == index {
+                unit.set_type(ty);
+            }
+        }
+    }
+
+    /// Gets the content at `pos`, given as a [`CellRef`](crate::cell_ref::CellRef) or in
+    /// `(col, row)`.
+    pub fn content_at(&self, pos: impl Into<(usize, usize)>) -> Option<&str> {
+        self.units.get(&pos.into()).map(|u| u.content.as_str())
     }
 
     pub fn get_display_pos(&self, pos: (usize, usize), corner: (usize, usize)) -> (usize, usize) {
@@ -66,31 +321,314 @@ impl Sheet {
         )
     }
 
+    /// Parses given string slice, detecting its layout as [`Layout::detect`] does.
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(buf: &str) -> Self {
-        let widths = Self::get_widths(buf, DEFAULT_TAB_SIZE);
-        let mut accum_widths = vec![0];
-        for i in 0..widths.len() {
-            accum_widths.push(widths[i] + accum_widths[i]);
+        Self::from_str_with_layout(buf, Layout::detect(buf))
+    }
+
+    /// Parses given string slice in the specified layout.
+    pub fn from_str_with_layout(buf: &str, layout: Layout) -> Self {
+        Self::parse(buf, layout, DEFAULT_TAB_SIZE)
+    }
+
+    /// Parses given string slice, detecting its layout, with tabs assumed to be `tab_size` wide.
+    pub fn from_str_with_tab_size(buf: &str, tab_size: usize) -> Self {
+        Self::parse_with(
+            buf,
+            &ParseOptions {
+                tab_size,
+                ..ParseOptions::default()
+            },
+        )
+    }
+
+    /// Parses given string slice as specified by `options`.
+    pub fn parse_with(buf: &str, options: &ParseOptions) -> Self {
+        let tab_size = options.tab_size.max(1);
+
+        // Comments are set aside, so that they neither take up rows nor widen columns.
+        let mut comments = vec![];
+        let mut lines = vec![];
+        for line in buf.lines() {
+            match &options.comment_prefix {
+                Some(prefix) if line.starts_with(prefix.as_str()) => comments.push(Comment {
+                    row: lines.len(),
+                    text: line.to_owned(),
+                }),
+                _ => lines.push(line),
+            }
+        }
+        let buf = match options.comment_prefix {
+            Some(_) => lines.join("\n"),
+            None => buf.to_owned(),
+        };
+
+        let layout = options
+            .layout
+            .unwrap_or_else(|| Layout::detect_with_tab_size(&buf, tab_size));
+        let mut sheet = Self::parse(&buf, layout, tab_size);
+        sheet.comment_prefix = options.comment_prefix.clone();
+        sheet.comments = comments;
+
+        sheet
+    }
+
+    fn parse(buf: &str, layout: Layout, tab_size: usize) -> Self {
+        let mut sheet = match layout {
+            Layout::Aligned => Self::parse_aligned(buf, tab_size),
+            Layout::Elastic => {
+                let (units, rows) = Self::parse_elastic(buf);
+                Self::from_units(units, (0, rows))
+            }
+            Layout::Spaces => {
+                let (units, rows) = Self::parse_spaces(buf);
+                Self::from_units(units, (0, rows))
+            }
+        };
+        sheet.layout = layout;
+        if sheet.tab_size != tab_size {
+            sheet.set_tab_size(tab_size);
         }
 
+        sheet
+    }
+
+    /// Reads every non-empty row below the first one as a record, using the contents of the
+    /// first row as the names of fields.
+    pub fn records<T: FromRecord>(&self) -> impl Iterator<Item = Result<T, RecordError>> + '_ {
+        let headers = self.headers();
+
+        (1..self.size.1)
+            .filter(|&row| !self.is_row_empty(row))
+            .map(move |row| T::from_record(&Record::new(self, &headers, row)))
+    }
+
+    /// Builds a sheet from records, with the names of fields in the first row.
+    pub fn from_records<T: ToRecord>(records: &[T]) -> Self {
         let mut units_map = HashMap::new();
 
-        let mut row: usize = 0;
-        for line in buf.lines() {
-            let mut col: usize = 0;
-            let mut items = line.split('\t');
-            while let Some(s) = items.next() {
+        for (col, name) in T::fields().into_iter().enumerate() {
+            units_map.insert((col, 0), Unit::from(name));
+        }
+        for (row, record) in records.iter().enumerate() {
+            for (col, content) in record.to_record().iter().enumerate() {
+                if !content.is_empty() {
+                    units_map.insert((col, row + 1), Unit::from(content.as_str()));
+                }
+            }
+        }
+
+        Self::from_units(units_map, (0, records.len() + 1))
+    }
+
+    /// Gets the names in the first row and the columns they are in.
+    fn headers(&self) -> Vec<(usize, &str)> {
+        (0..self.size.0)
+            .filter_map(|col| self.content_at((col, 0)).map(|name| (col, name)))
+            .collect()
+    }
+
+    /// Writes the sheet in its current layout.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut comments = self.comments.iter().peekable();
+        // Measured once rather than for every row.
+        let space_widths = match self.layout {
+            Layout::Spaces => self.get_space_widths(),
+            _ => vec![],
+        };
+
+        for row in 0..self.size.1 {
+            while let Some(comment) = comments.next_if(|comment| comment.row <= row) {
+                writer.write_all(comment.text.as_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+            let line = match self.layout {
+                Layout::Spaces => self.format_row_spaces(row, &space_widths),
+                layout => self.format_row(row, layout),
+            };
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        for comment in comments {
+            writer.write_all(comment.text.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the sheet to the file at `path`, which is either entirely replaced or left as it
+    /// was if anything fails. If `backup` is set, the previous file is kept at the same path
+    /// with a `~` appended.
+    pub fn write_to_file(&self, path: impl AsRef<Path>, backup: bool) -> io::Result<()> {
+        write_atomically(path.as_ref(), backup, |file| {
+            let mut writer = BufWriter::new(file);
+            self.write_to(&mut writer)?;
+            writer.flush()
+        })
+    }
+
+    /// Formats the row of `index` in given layout, without the trailing newline.
+    fn format_row(&self, index: usize, layout: Layout) -> String {
+        let mut line = String::new();
+
+        match layout {
+            Layout::Aligned => {
+                let mut count: usize = 0;
+                for col in 0..self.size.0 {
+                    if let Some(s) = self.content_at((col, index)) {
+                        line.push_str(&"\t".repeat(count));
+                        line.push_str(s);
+
+                        let width = Self::measure_width(s, self.tab_size);
+                        count = 1 + self.widths[col].saturating_sub(width);
+                    } else {
+                        count += self.widths[col];
+                    }
+                }
+            }
+            Layout::Elastic => {
+                let mut last: usize = 0;
+                for col in 0..self.size.0 {
+                    if let Some(s) = self.content_at((col, index)) {
+                        line.push_str(&"\t".repeat(col - last));
+                        line.push_str(s);
+                        last = col;
+                    }
+                }
+            }
+            Layout::Spaces => return self.format_row_spaces(index, &self.get_space_widths()),
+        }
+
+        line
+    }
+
+    /// Formats the row of `index` in [`Layout::Spaces`], with columns of `col_widths`, as
+    /// measured by [`Sheet::get_space_widths`].
+    fn format_row_spaces(&self, index: usize, col_widths: &[usize]) -> String {
+        let mut line = String::new();
+
+        let mut count: usize = 0;
+        for (col, col_width) in col_widths.iter().enumerate() {
+            if let Some(s) = self.content_at((col, index)) {
+                line.push_str(&" ".repeat(count));
+                line.push_str(s);
+                count = col_width - UnicodeWidthStr::width(s);
+            } else {
+                count += col_width;
+            }
+        }
+
+        line
+    }
+
+    /// Builds a sheet from parsed units, measuring column widths from content. The sheet is
+    /// at least of `size`, and grows to hold every unit.
+    fn from_units(units: HashMap<(usize, usize), Unit>, size: (usize, usize)) -> Self {
+        let cols = units.keys().map(|pos| pos.0 + 1).fold(size.0, usize::max);
+        let rows = units.keys().map(|pos| pos.1 + 1).fold(size.1, usize::max);
+
+        let mut sheet = Self {
+            units,
+            size: (cols, rows),
+            tab_size: DEFAULT_TAB_SIZE,
+            widths: vec![],
+            accum_widths: vec![],
+            layout: Layout::default(),
+            collapse_empty: false,
+            column_types: HashMap::new(),
+            comment_prefix: None,
+            comments: vec![],
+        };
+        sheet.widths = sheet.get_col_widths();
+        sheet.update_accum_widths();
+
+        sheet
+    }
+
+    /// Parses units separated by exactly one tab. Returns the units and the count of rows.
+    fn parse_elastic(buf: &str) -> (HashMap<(usize, usize), Unit>, usize) {
+        let mut units_map = HashMap::new();
+
+        let mut rows: usize = 0;
+        for (row, line) in buf.lines().enumerate() {
+            for (col, s) in line.split('\t').enumerate() {
                 if !s.is_empty() {
                     units_map.insert((col, row), Unit::from(s));
                 }
+            }
+
+            rows = row + 1;
+        }
+
+        (units_map, rows)
+    }
+
+    /// Parses units padded with spaces. Returns the units and the count of rows.
+    ///
+    /// A unit starts at the beginning of a line or after at least two spaces, so units
+    /// containing two consecutive spaces cannot be told apart from two separate units.
+    fn parse_spaces(buf: &str) -> (HashMap<(usize, usize), Unit>, usize) {
+        let mut lines: Vec<Vec<(usize, &str)>> = vec![];
 
-                let width = Self::measure_width(s, DEFAULT_TAB_SIZE);
-                let diff = widths[col].saturating_sub(width);
-                if diff > 0 {
-                    items.nth(diff - 1);
+        for line in buf.lines() {
+            let mut items = vec![];
+            // Byte index and display offset of the unit being read.
+            let mut start: Option<(usize, usize)> = None;
+            let mut offset: usize = 0;
+            let mut spaces: usize = 0;
+
+            for (i, c) in line.char_indices() {
+                if c == ' ' {
+                    spaces += 1;
+                    if spaces == SPACES_GAP {
+                        if let Some((begin, begin_offset)) = start.take() {
+                            items.push((begin_offset, line[begin..i].trim_end()));
+                        }
+                    }
+                } else {
+                    if start.is_none() {
+                        start = Some((i, offset));
+                    }
+                    spaces = 0;
                 }
+                offset += UnicodeWidthChar::width(c).unwrap_or(0);
+            }
+            if let Some((begin, begin_offset)) = start {
+                items.push((begin_offset, line[begin..].trim_end()));
+            }
+
+            lines.push(items);
+        }
+
+        let offsets: BTreeSet<usize> = lines.iter().flatten().map(|item| item.0).collect();
+        let offsets: Vec<usize> = offsets.into_iter().collect();
+
+        let mut units_map = HashMap::new();
+        for (row, items) in lines.iter().enumerate() {
+            for &(offset, s) in items {
+                let col = offsets.binary_search(&offset).unwrap();
+                units_map.insert((col, row), Unit::from(s));
+            }
+        }
+
+        (units_map, lines.len())
+    }
 
-                col += 1;
+    fn parse_aligned(buf: &str, tab_size: usize) -> Self {
+        let widths = Self::get_widths(buf, tab_size);
+        let mut accum_widths = vec![0];
+        for i in 0..widths.len() {
+            accum_widths.push(widths[i] + accum_widths[i]);
+        }
+
+        let mut units_map = HashMap::new();
+
+        let mut row: usize = 0;
+        for line in buf.lines() {
+            for (col, s) in Self::parse_aligned_line(line, &widths, tab_size) {
+                units_map.insert((col, row), Unit::from(s));
             }
 
             row += 1;
@@ -99,44 +637,250 @@ impl Sheet {
         Self {
             units: units_map,
             size: (widths.len(), row),
-            tab_size: DEFAULT_TAB_SIZE,
+            tab_size,
             widths,
             accum_widths,
+            layout: Layout::Aligned,
+            collapse_empty: false,
+            column_types: HashMap::new(),
+            comment_prefix: None,
+            comments: vec![],
+        }
+    }
+
+    /// Splits a line into the units in it and their columns, which are `widths` wide. Columns
+    /// past the end of `widths` are one tab stop wide.
+    fn parse_aligned_line<'a>(
+        line: &'a str,
+        widths: &[usize],
+        tab_size: usize,
+    ) -> Vec<(usize, &'a str)> {
+        let mut units = vec![];
+
+        let mut col: usize = 0;
+        let mut items = line.split('\t');
+        while let Some(s) = items.next() {
+            if !s.is_empty() {
+                units.push((col, s));
+            }
+
+            let width = Self::measure_width(s, tab_size);
+            let diff = widths.get(col).unwrap_or(&1).saturating_sub(width);
+            if diff > 0 {
+                items.nth(diff - 1);
+            }
+
+            col += 1;
         }
+
+        units
     }
 
-    pub fn edit(&mut self, pos: (usize, usize), buf: &str) {
+    /// Sets the content at `pos` from user input. Surrounding whitespace is trimmed, and empty
+    /// input clears the unit, removing its row and column if they become empty regardless of
+    /// [`Sheet::collapse_empty`].
+    pub fn edit(&mut self, pos: impl Into<(usize, usize)>, buf: &str) {
+        let pos = pos.into();
+        let buf = buf.trim();
+
         if buf.is_empty() {
             self.units.remove(&pos);
+            self.update_widths(&[pos], true);
+        } else {
+            self.set(pos, buf);
+        }
+    }
+
+    /// Sets the content at `pos`, growing the sheet if needed. Returns the previous content.
+    ///
+    /// Setting empty content is the same as [`Sheet::take`]. Contents containing tabs or line
+    /// breaks cannot be read back once written.
+    pub fn set(&mut self, pos: impl Into<(usize, usize)>, content: &str) -> Option<String> {
+        let pos = pos.into();
+        if content.is_empty() {
+            return self.take(pos);
+        }
+
+        let prev = self.units.insert(pos, Unit::from(content));
+        self.update_widths(&[pos], self.collapse_empty);
+
+        prev.map(|unit| unit.content)
+    }
+
+    /// Clears the unit at `pos`.
+    pub fn clear(&mut self, pos: impl Into<(usize, usize)>) {
+        self.take(pos);
+    }
+
+    /// Clears the unit at `pos`, returning its content. If [`Sheet::collapse_empty`] is set,
+    /// its row and column are removed if they become empty.
+    pub fn take(&mut self, pos: impl Into<(usize, usize)>) -> Option<String> {
+        let pos = pos.into();
+        let prev = self.units.remove(&pos);
+        self.update_widths(&[pos], self.collapse_empty);
+
+        prev.map(|unit| unit.content)
+    }
+
+    /// Swaps the contents at `a` and `b`.
+    pub fn swap(&mut self, a: impl Into<(usize, usize)>, b: impl Into<(usize, usize)>) {
+        let (a, b) = (a.into(), b.into());
+        if a == b {
+            return;
+        }
+
+        let unit_a = self.units.remove(&a);
+        let unit_b = self.units.remove(&b);
+        if let Some(unit) = unit_a {
+            self.units.insert(b, unit);
+        }
+        if let Some(unit) = unit_b {
+            self.units.insert(a, unit);
+        }
+
+        self.update_widths(&[a, b], self.collapse_empty);
+    }
+
+    /// Moves the content at `from` to `to`, leaving `from` empty. Returns the content previously
+    /// at `to`, which gets overwritten.
+    pub fn move_cell(
+        &mut self,
+        from: impl Into<(usize, usize)>,
+        to: impl Into<(usize, usize)>,
+    ) -> Option<String> {
+        let (from, to) = (from.into(), to.into());
+        if from == to {
+            return None;
+        }
+
+        let prev = match self.units.remove(&from) {
+            Some(unit) => self.units.insert(to, unit),
+            None => self.units.remove(&to),
+        };
+        self.update_widths(&[from, to], self.collapse_empty);
+
+        prev.map(|unit| unit.content)
+    }
+
+    /// Replaces the row of `index` with `contents`, from the first column on. Units past the
+    /// end of `contents` are cleared.
+    pub fn set_row<I, S>(&mut self, index: usize, contents: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let contents: Vec<S> = contents.into_iter().collect();
+        let positions: Vec<(usize, usize)> = (0..self.size.0.max(contents.len()))
+            .map(|col| (col, index))
+            .collect();
+
+        self.replace_units(&positions, &contents);
+    }
+
+    /// Replaces the column of `index` with `contents`, from the first row on. Units past the
+    /// end of `contents` are cleared.
+    pub fn set_column<I, S>(&mut self, index: usize, contents: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let contents: Vec<S> = contents.into_iter().collect();
+        let positions: Vec<(usize, usize)> = (0..self.size.1.max(contents.len()))
+            .map(|row| (index, row))
+            .collect();
+
+        self.replace_units(&positions, &contents);
+    }
+
+    /// Builds a sheet of the columns of `indices`, in order, with their widths measured again.
+    /// A column may be given more than once. The layout, tab size and comments are kept.
+    pub fn select_columns(&self, indices: &[usize]) -> Self {
+        let mut units = HashMap::new();
+        for (new_col, &col) in indices.iter().enumerate() {
+            for row in 0..self.size.1 {
+                if let Some(unit) = self.units.get(&(col, row)) {
+                    units.insert((new_col, row), unit.clone());
+                }
+            }
+        }
+
+        let mut sheet = Self::from_units(units, (indices.len(), self.size.1));
+        sheet.layout = self.layout;
+        sheet.set_tab_size(self.tab_size);
+        sheet.column_types = indices
+            .iter()
+            .enumerate()
+            .filter_map(|(new_col, col)| Some((new_col, *self.column_types.get(col)?)))
+            .collect();
+        sheet.comment_prefix = self.comment_prefix.clone();
+        sheet.comments = self.comments.clone();
+
+        sheet
+    }
+
+    /// Sets the units at `positions` to `contents` in order, clearing those left over.
+    fn replace_units<S: AsRef<str>>(&mut self, positions: &[(usize, usize)], contents: &[S]) {
+        for (i, &pos) in positions.iter().enumerate() {
+            match contents.get(i).map(AsRef::as_ref) {
+                Some(content) if !content.is_empty() => {
+                    self.units.insert(pos, Unit::from(content));
+                }
+                _ => {
+                    self.units.remove(&pos);
+                }
+            }
+        }
 
-            if self.is_col_empty(pos.0) {
-                self.remove_col(pos.0);
+        self.update_widths(positions, self.collapse_empty);
+    }
+
+    /// Resizes the sheet after the units at `positions` have changed, and measures the widths
+    /// of their columns again. Rows and columns left empty are removed if `collapse` is set.
+    fn update_widths(&mut self, positions: &[(usize, usize)], collapse: bool) {
+        for pos in positions {
+            if self.units.contains_key(pos) {
+                self.size.0 = self.size.0.max(pos.0 + 1);
+                self.size.1 = self.size.1.max(pos.1 + 1);
             }
-            if self.is_row_empty(pos.1) {
-                self.remove_row(pos.1);
+        }
+        self.widths.resize(self.size.0, 1);
+
+        for pos in positions {
+            if let Some(unit) = self.units.get_mut(pos) {
+                unit.set_type(self.column_types.get(&pos.0).copied());
             }
-        } else {
-            self.units
-                .entry(pos)
-                .and_modify(|mut unit| {
-                    unit.content = buf.trim().to_owned();
-                })
-                .or_insert_with(|| Unit::from(buf.trim()));
-
-            self.size.0 = self.size.0.max(pos.0 + 1);
-            self.size.1 = self.size.1.max(pos.1 + 1);
-
-            if let Some(&n) = self.widths.get(pos.0) {
-                let width = self.get_col_width(pos.0).unwrap();
-                if n != width {
-                    self.widths[pos.0] = width;
+        }
+
+        let mut cols: Vec<usize> = positions.iter().map(|pos| pos.0).collect();
+        cols.sort_unstable();
+        cols.dedup();
+        for &col in &cols {
+            if col < self.size.0 {
+                self.widths[col] = self.get_col_width(col).unwrap_or(1);
+            }
+        }
+
+        if collapse {
+            for &col in cols.iter().rev() {
+                if self.is_col_empty(col) {
+                    self.remove_col(col);
+                }
+            }
+
+            let mut rows: Vec<usize> = positions.iter().map(|pos| pos.1).collect();
+            rows.sort_unstable();
+            rows.dedup();
+            for &row in rows.iter().rev() {
+                if self.is_row_empty(row) {
+                    self.remove_row(row);
                 }
-            } else {
-                let width = Self::measure_width(buf, self.tab_size);
-                self.widths.push(width);
             }
         }
 
+        self.update_accum_widths();
+    }
+
+    fn update_accum_widths(&mut self) {
         let mut new_accum_widths = vec![0];
         for i in 0..self.widths.len() {
             new_accum_widths.push(self.widths[i] + new_accum_widths[i]);
@@ -154,9 +898,32 @@ impl Sheet {
             .max()
     }
 
+    /// Measures total widths of all columns at once, 1 for empty ones.
+    fn get_col_widths(&self) -> Vec<usize> {
+        let mut widths = vec![1; self.size.0];
+        for (pos, unit) in &self.units {
+            let width = Sheet::measure_width(&unit.content, self.tab_size);
+            widths[pos.0] = widths[pos.0].max(width);
+        }
+
+        widths
+    }
+
+    /// Measures widths of all columns at once in [`Layout::Spaces`], in terminal columns, gaps
+    /// included.
+    fn get_space_widths(&self) -> Vec<usize> {
+        let mut widths = vec![0; self.size.0];
+        for (pos, unit) in &self.units {
+            let width = UnicodeWidthStr::width(unit.content.as_str());
+            widths[pos.0] = widths[pos.0].max(width);
+        }
+
+        widths.into_iter().map(|width| width + SPACES_GAP).collect()
+    }
+
     /// Removes the columns of `index`. Will do nothing if `index` is out of bounds.
     fn remove_col(&mut self, index: usize) {
-        if !(index < self.size.0) {
+        if index >= self.size.0 {
             return;
         }
 
@@ -170,16 +937,66 @@ impl Sheet {
 
         self.widths.remove(index);
         self.size.0 = self.widths.len();
+
+        self.column_types = self
+            .column_types
+            .drain()
+            .filter(|&(col, _)| col != index)
+            .map(|(col, ty)| (if col > index { col - 1 } else { col }, ty))
+            .collect();
+
+        self.shift_formulas(Removal::Col(index));
+    }
+
+    /// Inserts a row read from `line` before that of `row`, in place of the comment of
+    /// `comment`, which comes before it.
+    fn insert_row(&mut self, comment: usize, row: usize, line: &str) {
+        for pos in (row..self.size.1)
+            .rev()
+            .flat_map(|r| (0..self.size.0).map(move |c| (c, r)))
+        {
+            if let Some(unit) = self.units.remove(&pos) {
+                self.units.insert((pos.0, pos.1 + 1), unit);
+            }
+        }
+        self.size.1 += 1;
+
+        // Comments after the one of the row move down along with the rows after it.
+        for later in &mut self.comments[comment..] {
+            later.row += 1;
+        }
+        self.rewrite_formulas(|content| insert_row_references(content, row));
+
+        let units: Vec<(usize, String)> = match self.layout {
+            Layout::Aligned => Self::parse_aligned_line(line, &self.widths, self.tab_size)
+                .into_iter()
+                .map(|(col, s)| (col, s.to_owned()))
+                .collect(),
+            Layout::Elastic => line
+                .split('\t')
+                .enumerate()
+                .map(|(col, s)| (col, s.to_owned()))
+                .collect(),
+            Layout::Spaces => Self::parse_spaces(line)
+                .0
+                .into_iter()
+                .map(|(pos, unit)| (pos.0, unit.content))
+                .collect(),
+        };
+        for (col, content) in units.iter().filter(|unit| !unit.1.is_empty()) {
+            self.set((*col, row), content);
+        }
     }
 
     /// Removes the row of `index`. Will do nothing if `index` is out of bounds.
     fn remove_row(&mut self, index: usize) {
-        if !(index < self.size.1) {
+        if index >= self.size.1 {
             return;
         }
 
         for col in 0..self.size.0 {
-            for row in index..self.size.1 {
+            self.units.remove(&(col, index));
+            for row in index + 1..self.size.1 {
                 if let Some(v) = self.units.remove(&(col, row)) {
                     self.units.insert((col, row - 1), v);
                 }
@@ -187,11 +1004,46 @@ impl Sheet {
         }
 
         self.size.1 -= 1;
+
+        // Comments before the rows after it move up along with them.
+        for comment in &mut self.comments {
+            if comment.row > index {
+                comment.row -= 1;
+            }
+        }
+        self.shift_formulas(Removal::Row(index));
+    }
+
+    /// Rewrites the references of formulas for the row or column of `removal` having been
+    /// removed, and measures the widths of their columns again.
+    fn shift_formulas(&mut self, removal: Removal) {
+        self.rewrite_formulas(|content| shift_references(content, removal));
+    }
+
+    /// Rewrites the contents of formulas with `rewrite`, which returns `None` to keep one as it
+    /// is, and measures the columns of those changed again.
+    fn rewrite_formulas(&mut self, rewrite: impl Fn(&str) -> Option<String>) {
+        let mut cols = vec![];
+        for (pos, unit) in self.units.iter_mut() {
+            if !is_formula(&unit.content) {
+                continue;
+            }
+            if let Some(content) = rewrite(&unit.content) {
+                unit.content = content;
+                cols.push(pos.0);
+            }
+        }
+
+        cols.sort_unstable();
+        cols.dedup();
+        for col in cols {
+            self.widths[col] = self.get_col_width(col).unwrap_or(1);
+        }
     }
 
     /// Checks if the column of `index` is empty.
     fn is_col_empty(&self, index: usize) -> bool {
-        for row in 0..self.size.0 {
+        for row in 0..self.size.1 {
             if self.units.contains_key(&(index, row)) {
                 return false;
             }
@@ -200,8 +1052,9 @@ impl Sheet {
         true
     }
 
+    /// Checks if the row of `index` is empty.
     fn is_row_empty(&self, index: usize) -> bool {
-        for col in 0..self.size.1 {
+        for col in 0..self.size.0 {
             if self.units.contains_key(&(col, index)) {
                 return false;
             }
@@ -274,29 +1127,330 @@ impl Default for Sheet {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "String", into = "String")
+)]
 pub struct Unit {
     content: String,
+    ty: CellType,
+    /// Whether `ty` is declared, rather than inferred from `content`.
+    declared: bool,
 }
 
 impl Unit {
     fn new() -> Self {
         Unit {
             content: String::new(),
+            ty: CellType::Text,
+            declared: false,
         }
     }
+
+    /// Creates a unit of declared type.
+    pub fn with_type(content: &str, ty: CellType) -> Self {
+        Unit {
+            content: content.to_owned(),
+            ty,
+            declared: true,
+        }
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn ty(&self) -> CellType {
+        self.ty
+    }
+
+    pub fn is_declared(&self) -> bool {
+        self.declared
+    }
+
+    /// Parses the content as the type of the unit. Content not of its declared type is read
+    /// as text.
+    pub fn value(&self) -> CellValue {
+        self.ty
+            .parse(&self.content)
+            .unwrap_or_else(|| CellValue::Text(self.content.clone()))
+    }
+
+    /// Declares the type of the unit, or infers it from the content with `None`.
+    fn set_type(&mut self, ty: Option<CellType>) {
+        self.declared = ty.is_some();
+        self.ty = ty.unwrap_or_else(|| CellType::infer(&self.content));
+    }
 }
 
 impl From<&str> for Unit {
     fn from(value: &str) -> Self {
         Unit {
             content: value.to_owned(),
+            ty: CellType::infer(value),
+            declared: false,
+        }
+    }
+}
+
+impl From<String> for Unit {
+    fn from(value: String) -> Self {
+        Unit {
+            ty: CellType::infer(&value),
+            content: value,
+            declared: false,
         }
     }
 }
 
+impl From<Unit> for String {
+    fn from(unit: Unit) -> Self {
+        unit.content
+    }
+}
+
 impl Default for Unit {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(sheet: &Sheet) -> String {
+        let mut buf = vec![];
+        sheet.write_to(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn detect_aligned() {
+        assert_eq!(Layout::detect("a\tb\nc\td\n"), Layout::Aligned);
+        assert_eq!(
+            Layout::detect("name\t\tprice\nlonger name\t3\n"),
+            Layout::Aligned
+        );
+        assert_eq!(Layout::detect("a b\nc\n"), Layout::Aligned);
+        assert_eq!(Layout::detect(""), Layout::Aligned);
+    }
+
+    #[test]
+    fn detect_elastic() {
+        // The second unit of the first row is read as two tab stops out of line.
+        let buf = "name\tprice\nlongername\t3\n";
+        assert_eq!(Layout::detect(buf), Layout::Elastic);
+
+        let sheet = Sheet::from_str(buf);
+        assert_eq!(sheet.size(), (2, 2));
+        assert_eq!(sheet.content_at((1, 0)), Some("price"));
+        assert_eq!(sheet.content_at((1, 1)), Some("3"));
+        assert_eq!(write(&sheet), buf);
+    }
+
+    #[test]
+    fn detect_spaces() {
+        let buf = "name    price\nlonger  3\n";
+        assert_eq!(Layout::detect(buf), Layout::Spaces);
+
+        let sheet = Sheet::from_str(buf);
+        assert_eq!(sheet.size(), (2, 2));
+        assert_eq!(sheet.content_at((0, 1)), Some("longer"));
+        assert_eq!(write(&sheet), buf);
+    }
+
+    #[test]
+    fn explicit_layout_is_not_detected() {
+        let sheet = Sheet::from_str_with_layout("a  b\n", Layout::Aligned);
+        assert_eq!(sheet.size(), (1, 1));
+        assert_eq!(sheet.content_at((0, 0)), Some("a  b"));
+    }
+
+    #[test]
+    fn cleared_col_keeps_no_width() {
+        let mut sheet = Sheet::from_str("a\tlonger than a tab\tb\n");
+        assert_eq!(sheet.width_at(1), Some(&3));
+
+        sheet.clear((1, 0));
+        assert_eq!(sheet.size(), (3, 1));
+        assert_eq!(sheet.width_at(1), Some(&1));
+        assert_eq!(sheet.accum_width_at(3), Some(&3));
+    }
+
+    #[test]
+    fn set_and_take() {
+        let mut sheet = Sheet::from_str("a\tb\nc\n");
+        assert_eq!(sheet.set((3, 2), "d"), None);
+        assert_eq!(sheet.size(), (4, 3));
+        assert_eq!(sheet.set((0, 0), "A"), Some("a".to_owned()));
+        assert_eq!(sheet.set((0, 0), ""), Some("A".to_owned()));
+        assert_eq!(sheet.content_at((0, 0)), None);
+        assert_eq!(sheet.take((0, 1)), Some("c".to_owned()));
+        assert_eq!(sheet.take((0, 1)), None);
+    }
+
+    #[test]
+    fn collapse_empty() {
+        let buf = "a\tb\nc\td\ne\n";
+
+        // Off by default: rows and columns stay even when emptied.
+        let mut sheet = Sheet::from_str(buf);
+        assert!(!sheet.collapse_empty());
+        sheet.clear((1, 0));
+        sheet.clear((1, 1));
+        assert_eq!(sheet.take((0, 2)), Some("e".to_owned()));
+        assert_eq!(sheet.size(), (2, 3));
+        assert_eq!(write(&sheet), "a\nc\n\n");
+
+        let mut sheet = Sheet::from_str(buf);
+        sheet.set_collapse_empty(true);
+        sheet.clear((1, 0));
+        assert_eq!(sheet.size(), (2, 3));
+        sheet.clear((1, 1));
+        assert_eq!(sheet.size(), (1, 3));
+        assert_eq!(sheet.take((0, 2)), Some("e".to_owned()));
+        assert_eq!(sheet.size(), (1, 2));
+        assert_eq!(write(&sheet), "a\nc\n");
+    }
+
+    #[test]
+    fn swap_and_move() {
+        let mut sheet = Sheet::from_str("a\tb\nc\n");
+        sheet.swap((0, 0), (1, 1));
+        assert_eq!(sheet.content_at((0, 0)), None);
+        assert_eq!(sheet.content_at((1, 1)), Some("a"));
+        sheet.swap((1, 0), (1, 0));
+        assert_eq!(sheet.content_at((1, 0)), Some("b"));
+
+        assert_eq!(sheet.move_cell((1, 0), (1, 1)), Some("a".to_owned()));
+        assert_eq!(sheet.content_at((1, 0)), None);
+        assert_eq!(sheet.content_at((1, 1)), Some("b"));
+        // Moving an empty unit clears the target.
+        assert_eq!(sheet.move_cell((0, 0), (0, 1)), Some("c".to_owned()));
+        assert_eq!(sheet.content_at((0, 1)), None);
+        assert_eq!(sheet.move_cell((2, 3), (2, 3)), None);
+        assert_eq!(sheet.size(), (2, 2));
+    }
+
+    #[test]
+    fn set_rows_and_columns() {
+        let mut sheet = Sheet::from_str("a\tb\tc\nd\te\tf\n");
+        sheet.set_row(0, ["x", "", "y", "z"]);
+        assert_eq!(sheet.size(), (4, 2));
+        assert_eq!(write(&sheet), "x\t\ty\tz\nd\te\tf\n");
+        sheet.set_row(1, ["w"]);
+        assert_eq!(write(&sheet), "x\t\ty\tz\nw\n");
+
+        sheet.set_column(2, ["1", "2", "3"]);
+        assert_eq!(sheet.size(), (4, 3));
+        assert_eq!(sheet.content_at((2, 2)), Some("3"));
+        sheet.set_column(2, Vec::<String>::new());
+        assert_eq!(sheet.column(2).count(), 0);
+    }
+
+    #[test]
+    fn widths_measured_at_once() {
+        let sheet = Sheet::from_str("a\tb\nlonger than a tab\tc\n");
+        assert_eq!(sheet.width_at(0), Some(&3));
+        assert_eq!(sheet.width_at(1), Some(&1));
+        assert_eq!(sheet.accum_width_at(2), Some(&4));
+    }
+
+    #[test]
+    fn empty_col_in_tall_sheet() {
+        let sheet = Sheet::from_str("a\nb\n\tc");
+        assert_eq!(sheet.size(), (2, 3));
+        assert!(!sheet.is_col_empty(1));
+    }
+
+    #[test]
+    fn empty_row_in_wide_sheet() {
+        let sheet = Sheet::from_str("a\tb\tc\n\t\td");
+        assert_eq!(sheet.size(), (3, 2));
+        assert!(!sheet.is_row_empty(1));
+    }
+
+    #[test]
+    fn comments_follow_removed_rows() {
+        let options = ParseOptions {
+            comment_prefix: Some("#".to_owned()),
+            ..ParseOptions::default()
+        };
+        let mut sheet = Sheet::parse_with("a\n# b\nb\n\n# d\nd\n# end\n", &options);
+        sheet.set_collapse_empty(true);
+        sheet.edit((0, 0), "");
+        assert_eq!(write(&sheet), "# b\nb\n\n# d\nd\n# end\n");
+
+        sheet.edit((0, 1), "x");
+        sheet.edit((0, 1), "");
+        assert_eq!(write(&sheet), "# b\nb\n# d\nd\n# end\n");
+    }
+
+    #[test]
+    fn comment_prefix_reclassifies_rows() {
+        let buf = "# a\nb\t=A5\n#c\td\ne\n\tf\n";
+        let mut sheet = Sheet::from_str(buf);
+        assert_eq!(sheet.size(), (2, 5));
+
+        sheet.set_comment_prefix(Some("#"));
+        assert_eq!(sheet.comment_prefix(), Some("#"));
+        assert_eq!(sheet.size(), (2, 3));
+        assert_eq!(
+            sheet.comments().iter().map(|c| c.row).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        // The formula still refers to `f`, now a row closer to it.
+        assert_eq!(sheet.content_at((1, 0)), Some("=A3"));
+        assert_eq!(write(&sheet), "# a\nb\t=A3\n#c\td\ne\n\tf\n");
+
+        sheet.set_comment_prefix(Some("#c"));
+        assert_eq!(sheet.size(), (2, 4));
+        assert_eq!(sheet.content_at((0, 0)), Some("# a"));
+        assert_eq!(sheet.content_at((1, 1)), Some("=A4"));
+
+        sheet.set_comment_prefix(None);
+        assert!(sheet.comments().is_empty());
+        assert_eq!(sheet.content_at((1, 2)), Some("d"));
+        assert_eq!(write(&sheet), buf);
+    }
+
+    #[test]
+    fn parse_aligned_gaps() {
+        // Units wider than a tab stop take up more of them, and an empty unit is one more.
+        let buf = "name\t\tprice\nlonger name\t3\n\t\t\tlast\n";
+        let sheet = Sheet::from_str_with_layout(buf, Layout::Aligned);
+        assert_eq!(sheet.size(), (3, 3));
+        assert_eq!(sheet.content_at((1, 1)), Some("3"));
+        assert_eq!(sheet.content_at((2, 2)), Some("last"));
+        assert_eq!(sheet.content_at((0, 2)), None);
+
+        let sheet = Sheet::from_str_with_tab_size("ab\tc\nabcd\td\n", 4);
+        assert_eq!(sheet.content_at((1, 1)), Some("d"));
+        assert_eq!(sheet.size(), (2, 2));
+    }
+
+    #[test]
+    fn parse_elastic_and_spaces() {
+        let sheet = Sheet::from_str_with_layout("a\t\tc\n\tb\n", Layout::Elastic);
+        assert_eq!(sheet.size(), (3, 2));
+        assert_eq!(sheet.content_at((2, 0)), Some("c"));
+        assert_eq!(sheet.content_at((1, 1)), Some("b"));
+
+        let buf = "first name  age\nAda         36\n            12\n";
+        let sheet = Sheet::from_str_with_layout(buf, Layout::Spaces);
+        assert_eq!(sheet.content_at((0, 0)), Some("first name"));
+        assert_eq!(sheet.content_at((1, 2)), Some("12"));
+        assert_eq!(sheet.content_at((0, 2)), None);
+        assert_eq!(write(&sheet), buf);
+    }
+
+    #[test]
+    fn parse_crlf() {
+        let sheet = Sheet::from_str("a\tb\r\nc\td\r\n");
+        assert_eq!(sheet.content_at((1, 0)), Some("b"));
+        assert_eq!(sheet.content_at((1, 1)), Some("d"));
+    }
+}
@@ -0,0 +1,360 @@
+//! Reading and writing a [`Sheet`] in formats other than its own, such as CSV.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+    mem,
+    path::Path,
+    str::FromStr,
+};
+
+use super::{Layout, Sheet, Unit};
+
+/// A format a sheet can be converted from or to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Plain text, in any [`Layout`].
+    Text,
+    /// Tab-separated values.
+    Tsv,
+    /// Comma-separated values, with fields quoted as in RFC 4180.
+    Csv,
+    /// A Markdown table, with the first row as its header.
+    Markdown,
+    /// An HTML table, with the first row as its header.
+    Html,
+    /// A JSON array of rows, each an array of strings.
+    Json,
+}
+
+impl Format {
+    /// Guesses the format from the extension of `path`, case-insensitively.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        extension.to_ascii_lowercase().parse().ok()
+    }
+
+    /// Checks if sheets can be read in the format. Only the formats of delimited text can.
+    pub fn is_readable(&self) -> bool {
+        matches!(self, Format::Text | Format::Tsv | Format::Csv)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "txt" | "text" => Ok(Format::Text),
+            "tsv" => Ok(Format::Tsv),
+            "csv" => Ok(Format::Csv),
+            "md" | "markdown" => Ok(Format::Markdown),
+            "html" | "htm" => Ok(Format::Html),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Text => "txt",
+            Format::Tsv => "tsv",
+            Format::Csv => "csv",
+            Format::Markdown => "md",
+            Format::Html => "html",
+            Format::Json => "json",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Sheet {
+    /// Parses given string slice in `format`. Returns `None` if sheets cannot be read in it.
+    ///
+    /// Sheets read from formats other than [`Format::Text`] are laid out in
+    /// [`Layout::Aligned`]. As units are single lines without tabs, line breaks and tabs in
+    /// fields are read as spaces.
+    pub fn from_str_as(buf: &str, format: Format) -> Option<Self> {
+        let sheet = match format {
+            Format::Text => Self::from_str(buf),
+            Format::Tsv => Self::from_fields(buf.lines().map(|line| line.split('\t'))),
+            Format::Csv => Self::from_fields(parse_csv(buf)),
+            _ => return None,
+        };
+
+        Some(sheet)
+    }
+
+    /// Builds a sheet from rows of fields, in order.
+    fn from_fields<R, F>(rows: R) -> Self
+    where
+        R: IntoIterator<Item = F>,
+        F: IntoIterator,
+        F::Item: AsRef<str>,
+    {
+        let mut units = HashMap::new();
+        let mut size = (0, 0);
+
+        for (row, fields) in rows.into_iter().enumerate() {
+            for (col, field) in fields.into_iter().enumerate() {
+                let content = field.as_ref().replace(['\t', '\r', '\n'], " ");
+                let content = content.trim();
+                if !content.is_empty() {
+                    units.insert((col, row), Unit::from(content));
+                }
+                size.0 = size.0.max(col + 1);
+            }
+            size.1 = row + 1;
+        }
+
+        let mut sheet = Self::from_units(units, size);
+        sheet.layout = Layout::default();
+
+        sheet
+    }
+
+    /// Writes the sheet in `format`. Comments are only written in [`Format::Text`], and
+    /// formulas are written as they are rather than their values.
+    pub fn write_as<W: Write>(&self, writer: &mut W, format: Format) -> io::Result<()> {
+        match format {
+            Format::Text => self.write_to(writer),
+            Format::Tsv => self.write_rows(writer, |row| row.join("\t")),
+            Format::Csv => self.write_rows(writer, |row| {
+                let fields: Vec<String> = row.iter().map(|s| quote_csv(s)).collect();
+                fields.join(",")
+            }),
+            Format::Markdown => self.write_markdown(writer),
+            Format::Html => self.write_html(writer),
+            Format::Json => self.write_json(writer),
+        }
+    }
+
+    /// Gets the contents of the row of `index`, one for each column, empty for empty units.
+    fn row_contents(&self, index: usize) -> Vec<&str> {
+        (0..self.size.0)
+            .map(|col| self.content_at((col, index)).unwrap_or_default())
+            .collect()
+    }
+
+    /// Writes each row as a line formatted by `format_row`.
+    fn write_rows<W, F>(&self, writer: &mut W, format_row: F) -> io::Result<()>
+    where
+        W: Write,
+        F: Fn(&[&str]) -> String,
+    {
+        for row in 0..self.size.1 {
+            writeln!(writer, "{}", format_row(&self.row_contents(row)))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_markdown<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        if self.size.1 == 0 {
+            return Ok(());
+        }
+
+        let format_row = |row: &[&str]| {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|s| s.replace('\\', "\\\\").replace('|', "\\|"))
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        writeln!(writer, "{}", format_row(&self.row_contents(0)))?;
+        writeln!(writer, "{}", format_row(&vec!["---"; self.size.0]))?;
+        for row in 1..self.size.1 {
+            writeln!(writer, "{}", format_row(&self.row_contents(row)))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_html<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let write_row = |writer: &mut W, row: usize, tag: &str| {
+            let cells: String = self
+                .row_contents(row)
+                .iter()
+                .map(|s| format!("<{}>{}</{}>", tag, escape_html(s), tag))
+                .collect();
+            writeln!(writer, "    <tr>{}</tr>", cells)
+        };
+
+        writeln!(writer, "<table>")?;
+        if self.size.1 > 0 {
+            writeln!(writer, "  <thead>")?;
+            write_row(writer, 0, "th")?;
+            writeln!(writer, "  </thead>")?;
+        }
+        if self.size.1 > 1 {
+            writeln!(writer, "  <tbody>")?;
+            for row in 1..self.size.1 {
+                write_row(writer, row, "td")?;
+            }
+            writeln!(writer, "  </tbody>")?;
+        }
+        writeln!(writer, "</table>")
+    }
+
+    fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "[")?;
+        for row in 0..self.size.1 {
+            let fields: Vec<String> = self
+                .row_contents(row)
+                .iter()
+                .map(|s| quote_json(s))
+                .collect();
+            let separator = if row + 1 < self.size.1 { "," } else { "" };
+            writeln!(writer, "  [{}]{}", fields.join(", "), separator)?;
+        }
+        writeln!(writer, "]")
+    }
+}
+
+/// Splits CSV into rows of fields. Quoted fields may contain commas, line breaks and quotes
+/// written twice.
+fn parse_csv(buf: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut started = false;
+    let mut quoted = false;
+
+    let mut chars = buf.chars().peekable();
+    while let Some(c) = chars.next() {
+        let was_started = mem::replace(&mut started, true);
+        match c {
+            '"' if quoted => {
+                if chars.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if !was_started => quoted = true,
+            ',' if !quoted => {
+                row.push(mem::take(&mut field));
+                started = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => started = was_started,
+            '\n' if !quoted => {
+                row.push(mem::take(&mut field));
+                rows.push(mem::take(&mut row));
+                started = false;
+            }
+            c => field.push(c),
+        }
+    }
+    if started || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+/// Quotes a CSV field if it contains anything that would otherwise end it.
+fn quote_csv(s: &str) -> String {
+    if s.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn quote_json(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(sheet: &Sheet, format: Format) -> String {
+        let mut buf = vec![];
+        sheet.write_as(&mut buf, format).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let sheet = Sheet::from_str("a,b\t\"quoted\"\t\n\t\"\"\tplain\n");
+        let csv = write(&sheet, Format::Csv);
+        assert_eq!(csv, "\"a,b\",\"\"\"quoted\"\"\",\n,\"\"\"\"\"\",plain\n");
+
+        let read = Sheet::from_str_as(&csv, Format::Csv).unwrap();
+        assert_eq!(read.size(), sheet.size());
+        assert_eq!(read.content_at((0, 0)), Some("a,b"));
+        assert_eq!(read.content_at((1, 0)), Some("\"quoted\""));
+        assert_eq!(read.content_at((1, 1)), Some("\"\""));
+        assert_eq!(read.content_at((2, 1)), Some("plain"));
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(parse_csv(""), Vec::<Vec<String>>::new());
+        assert_eq!(parse_csv("\"\""), [[""]]);
+        assert_eq!(parse_csv("\"\"\n"), [[""]]);
+        assert_eq!(
+            parse_csv("a,\r\n\"b\nc\",\"d\"\"\""),
+            [["a", ""], ["b\nc", "d\""]]
+        );
+    }
+
+    #[test]
+    fn markdown_escapes() {
+        let sheet = Sheet::from_str("a|b\tc\\|d\n");
+        assert_eq!(
+            write(&sheet, Format::Markdown),
+            "| a\\|b | c\\\\\\|d |\n| --- | --- |\n"
+        );
+    }
+
+    #[test]
+    fn html_escapes() {
+        let sheet = Sheet::from_str("<a href=\"x\">\n&amp;\n");
+        assert_eq!(
+            write(&sheet, Format::Html),
+            "<table>\n  <thead>\n    <tr><th>&lt;a href=&quot;x&quot;&gt;</th></tr>\n  </thead>\n  \
+             <tbody>\n    <tr><td>&amp;amp;</td></tr>\n  </tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let sheet = Sheet::from_str("\"q\"\tback\\slash\n\u{7}\n");
+        let json = write(&sheet, Format::Json);
+        let rows: Vec<Vec<String>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(rows, [["\"q\"", "back\\slash"], ["\u{7}", ""]]);
+    }
+}
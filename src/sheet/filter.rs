@@ -0,0 +1,119 @@
+//! Selecting the rows of a [`Sheet`] whose units match conditions.
+
+use regex::Regex;
+
+use super::Sheet;
+
+/// A condition on the units in a column.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub col: usize,
+    pub condition: Condition,
+}
+
+impl Filter {
+    pub fn new(col: usize, condition: Condition) -> Self {
+        Self { col, condition }
+    }
+
+    /// Checks if the row of `row` in `sheet` matches the filter.
+    pub fn matches(&self, sheet: &Sheet, row: usize) -> bool {
+        self.condition.matches(sheet.content_at((self.col, row)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// The content is exactly the string.
+    Equals(String),
+    /// The content contains the string.
+    Contains(String),
+    /// The content matches the regular expression.
+    Matches(Regex),
+    /// The content is a number, and compares to the value as specified.
+    Compare(Comparison, f64),
+}
+
+impl Condition {
+    /// Checks if given content matches the condition. Empty units only match an empty
+    /// [`Condition::Equals`].
+    pub fn matches(&self, content: Option<&str>) -> bool {
+        let s = content.unwrap_or_default();
+
+        match self {
+            Condition::Equals(value) => s == value,
+            Condition::Contains(value) => content.is_some() && s.contains(value.as_str()),
+            Condition::Matches(regex) => content.is_some() && regex.is_match(s),
+            Condition::Compare(comparison, value) => match s.trim().parse::<f64>() {
+                Ok(n) => comparison.compare(n, *value),
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn compare(&self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Less => a < b,
+            Comparison::LessEqual => a <= b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterEqual => a >= b,
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+        }
+    }
+}
+
+impl Sheet {
+    /// Gets the rows matching all `filters`, in order.
+    pub fn filter_rows(&self, filters: &[Filter]) -> Vec<usize> {
+        (0..self.size.1)
+            .filter(|&row| filters.iter().all(|filter| filter.matches(self, row)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    #[test]
+    fn conditions() {
+        assert!(Condition::Equals(String::new()).matches(None));
+        assert!(!Condition::Equals("a".to_owned()).matches(Some("a ")));
+        assert!(Condition::Contains("b c".to_owned()).matches(Some("ab cd")));
+        assert!(!Condition::Contains(String::new()).matches(None));
+        assert!(Condition::Matches(Regex::new("^[0-9]+$").unwrap()).matches(Some("42")));
+        assert!(!Condition::Matches(Regex::new(".*").unwrap()).matches(None));
+        assert!(Condition::Compare(Comparison::LessEqual, 2.0).matches(Some(" 2 ")));
+        assert!(!Condition::Compare(Comparison::NotEqual, 2.0).matches(Some("x")));
+    }
+
+    #[test]
+    fn rows_match_all_filters() {
+        let sheet = Sheet::from_str("a\t1\nb\t2\nab\t3\n\t4\n");
+        let filters = [
+            Filter::new(0, Condition::Contains("a".to_owned())),
+            Filter::new(1, Condition::Compare(Comparison::Greater, 1.0)),
+        ];
+        assert_eq!(sheet.filter_rows(&filters), [2]);
+        assert_eq!(sheet.filter_rows(&[]), [0, 1, 2, 3]);
+        assert_eq!(
+            sheet.filter_rows(&[Filter::new(0, Condition::Equals(String::new()))]),
+            [3]
+        );
+    }
+}
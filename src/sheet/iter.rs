@@ -0,0 +1,214 @@
+//! Iterating over the units of a [`Sheet`].
+
+use std::{ops::RangeInclusive, vec};
+
+use super::Sheet;
+
+impl Sheet {
+    /// Iterates over all non-empty units, row by row.
+    pub fn cells(&self) -> Cells<'_> {
+        self.range((0, 0)..=self.last_pos()).cells()
+    }
+
+    /// Iterates over the non-empty units in the row of `index`.
+    pub fn row(&self, index: usize) -> Cells<'_> {
+        self.range((0, index)..=(self.last_pos().0, index)).cells()
+    }
+
+    /// Iterates over the non-empty units in the column of `index`.
+    pub fn column(&self, index: usize) -> Cells<'_> {
+        self.range((index, 0)..=(index, self.last_pos().1)).cells()
+    }
+
+    /// Iterates over every row, including empty ones, so that the n-th item is the row of `n`.
+    pub fn rows(&self) -> impl Iterator<Item = Cells<'_>> {
+        (0..self.size.1).map(|row| self.row(row))
+    }
+
+    /// Gets the position of the lower right corner. Represented in `(col, row)`.
+    fn last_pos(&self) -> (usize, usize) {
+        (self.size.0.saturating_sub(1), self.size.1.saturating_sub(1))
+    }
+
+    /// Gets a view of the units between two corners, both inclusive. Given as a
+    /// [`CellRange`](crate::cell_ref::CellRange) or in `(col, row)`.
+    pub fn range(&self, range: impl Into<RangeInclusive<(usize, usize)>>) -> RangeView<'_> {
+        let (start, end) = range.into().into_inner();
+
+        RangeView {
+            sheet: self,
+            start: (start.0.min(end.0), start.1.min(end.1)),
+            end: (start.0.max(end.0), start.1.max(end.1)),
+        }
+    }
+}
+
+/// A rectangular view into a sheet.
+#[derive(Clone, Copy)]
+pub struct RangeView<'a> {
+    pub(super) sheet: &'a Sheet,
+    /// Upper left corner. Represented in `(col, row)`.
+    start: (usize, usize),
+    /// Lower right corner, inclusive. Represented in `(col, row)`.
+    end: (usize, usize),
+}
+
+impl<'a> RangeView<'a> {
+    pub fn start(&self) -> (usize, usize) {
+        self.start
+    }
+
+    pub fn end(&self) -> (usize, usize) {
+        self.end
+    }
+
+    /// Checks if `pos` lies in the view.
+    pub fn contains(&self, pos: (usize, usize)) -> bool {
+        (self.start.0..=self.end.0).contains(&pos.0) && (self.start.1..=self.end.1).contains(&pos.1)
+    }
+
+    /// Gets the lower right corner of the part of the view inside the sheet, or `None` if the
+    /// view lies wholly outside it.
+    pub(super) fn clipped_end(&self) -> Option<(usize, usize)> {
+        let last = self.sheet.last_pos();
+        let end = (self.end.0.min(last.0), self.end.1.min(last.1));
+
+        (self.start.0 <= end.0 && self.start.1 <= end.1).then_some(end)
+    }
+
+    /// Iterates over the non-empty units in the view, row by row.
+    pub fn cells(&self) -> Cells<'a> {
+        let end = match self.clipped_end() {
+            Some(end) => end,
+            None => {
+                return Cells {
+                    inner: vec![].into_iter(),
+                }
+            }
+        };
+        let area = (end.0 - self.start.0 + 1).saturating_mul(end.1 - self.start.1 + 1);
+
+        // Walk through whichever is smaller: the positions in the view, or the units.
+        let mut cells: Vec<((usize, usize), &'a str)> = if area <= self.sheet.units.len() {
+            (self.start.1..=end.1)
+                .flat_map(|row| (self.start.0..=end.0).map(move |col| (col, row)))
+                .filter_map(|pos| self.sheet.content_at(pos).map(|s| (pos, s)))
+                .collect()
+        } else {
+            self.sheet
+                .units
+                .iter()
+                .filter(|u| self.contains(*u.0))
+                .map(|(&pos, unit)| (pos, unit.content.as_str()))
+                .collect()
+        };
+        cells.sort_unstable_by_key(|cell| (cell.0 .1, cell.0 .0));
+
+        Cells {
+            inner: cells.into_iter(),
+        }
+    }
+
+    /// Iterates over every row in the view inside the sheet, including empty ones.
+    pub fn rows(&self) -> impl Iterator<Item = Cells<'a>> + 'a {
+        let view = *self;
+        let rows = match self.clipped_end() {
+            Some(end) => self.start.1..end.1 + 1,
+            None => 0..0,
+        };
+
+        rows.map(move |row| {
+            view.sheet
+                .range((view.start.0, row)..=(view.end.0, row))
+                .cells()
+        })
+    }
+}
+
+/// An iterator over non-empty units, yielding their positions in `(col, row)` and contents.
+pub struct Cells<'a> {
+    inner: vec::IntoIter<((usize, usize), &'a str)>,
+}
+
+impl<'a> Iterator for Cells<'a> {
+    type Item = ((usize, usize), &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Cells<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl ExactSizeIterator for Cells<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell_ref::CellRange;
+
+    fn positions(cells: Cells<'_>) -> Vec<(usize, usize)> {
+        cells.map(|cell| cell.0).collect()
+    }
+
+    #[test]
+    fn sparse_cells() {
+        let sheet = Sheet::from_str("a\t\tc\n\n\te\n");
+        assert_eq!(
+            sheet.cells().collect::<Vec<_>>(),
+            [((0, 0), "a"), ((2, 0), "c"), ((1, 2), "e")]
+        );
+        assert_eq!(sheet.cells().next_back(), Some(((1, 2), "e")));
+        assert_eq!(sheet.cells().len(), 3);
+
+        assert_eq!(positions(sheet.row(0)), [(0, 0), (2, 0)]);
+        assert_eq!(positions(sheet.row(1)), []);
+        assert_eq!(positions(sheet.column(1)), [(1, 2)]);
+
+        let rows: Vec<usize> = sheet.rows().map(|row| row.len()).collect();
+        assert_eq!(rows, [2, 0, 1]);
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let sheet = Sheet::from_str("a\tb\nc\n");
+        assert_eq!(sheet.row(2).count(), 0);
+        assert_eq!(sheet.row(usize::MAX).count(), 0);
+        assert_eq!(sheet.column(5).count(), 0);
+        assert_eq!(sheet.column(usize::MAX).count(), 0);
+    }
+
+    #[test]
+    fn range_bounds() {
+        let sheet = Sheet::from_str("a\tb\tc\nd\te\tf\ng\th\ti\n");
+
+        // Corners may be given in any order.
+        let view = sheet.range((2, 1)..=(1, 0));
+        assert_eq!((view.start(), view.end()), ((1, 0), (2, 1)));
+        assert!(view.contains((1, 1)));
+        assert!(!view.contains((0, 1)));
+        assert_eq!(positions(view.cells()), [(1, 0), (2, 0), (1, 1), (2, 1)]);
+
+        let range: CellRange = "B2:C3".parse().unwrap();
+        assert_eq!(
+            positions(sheet.range(range).cells()),
+            [(1, 1), (2, 1), (1, 2), (2, 2)]
+        );
+
+        // Views reaching past the sheet are clipped to it.
+        let view = sheet.range((1, 2)..=(usize::MAX, usize::MAX));
+        assert_eq!(positions(view.cells()), [(1, 2), (2, 2)]);
+        assert_eq!(view.rows().count(), 1);
+        let view = sheet.range((3, 0)..=(usize::MAX, usize::MAX));
+        assert_eq!(view.cells().count(), 0);
+        assert_eq!(view.rows().count(), 0);
+    }
+}
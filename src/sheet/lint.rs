@@ -0,0 +1,313 @@
+//! Finding problems in the text of a [`Sheet`], such as units out of line with their columns.
+
+use std::fmt;
+
+use super::{Layout, ParseOptions, Sheet};
+
+/// A problem found in the text of a sheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// Line of the problem, comments included. Zero-indexed.
+    pub line: usize,
+    /// Offset in bytes of the problem from the start of its line. Zero-indexed.
+    pub byte: usize,
+    pub kind: IssueKind,
+}
+
+/// Lines and bytes are displayed counting from 1, as in `3:14: trailing whitespace`.
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line + 1, self.byte + 1, self.kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The tabs before a unit are not as many as the widths of the columns, as read from the
+    /// text, call for.
+    Padding {
+        expected: usize,
+        found: usize,
+    },
+    /// A unit is wider than its column. Widths are in tab stops.
+    TooWide {
+        width: usize,
+        col_width: usize,
+    },
+    /// Spaces and tabs are mixed between units.
+    MixedWhitespace,
+    TrailingWhitespace,
+    /// The line ends with `\r\n`.
+    Crlf,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueKind::Padding { expected, found } => write!(
+                f,
+                "expected {} before the unit, found {}",
+                count_tabs(*expected),
+                found
+            ),
+            IssueKind::TooWide { width, col_width } => write!(
+                f,
+                "unit is {} tab stops wide, wider than its column of {}",
+                width, col_width
+            ),
+            IssueKind::MixedWhitespace => write!(f, "spaces and tabs mixed"),
+            IssueKind::TrailingWhitespace => write!(f, "trailing whitespace"),
+            IssueKind::Crlf => write!(f, "line ends with CRLF"),
+        }
+    }
+}
+
+/// A unit in a line, with what precedes it.
+struct Item<'a> {
+    content: &'a str,
+    /// Offset in bytes of the tabs before the unit.
+    byte: usize,
+    tabs: usize,
+}
+
+impl Sheet {
+    /// Finds problems in given string slice, read as specified by `options`, in order.
+    ///
+    /// Units are checked against the columns of [`Layout::Aligned`] unless `options` gives
+    /// another layout, in which tabs are not padding.
+    pub fn lint(buf: &str, options: &ParseOptions) -> Vec<Issue> {
+        let mut issues = vec![];
+
+        let mut lines: Vec<&str> = buf.split('\n').collect();
+        if lines.last() == Some(&"") {
+            lines.pop();
+        }
+        let is_comment = |line: &str| {
+            options
+                .comment_prefix
+                .as_ref()
+                .is_some_and(|prefix| line.starts_with(prefix.as_str()))
+        };
+
+        for (index, raw) in lines.iter().enumerate() {
+            let line = match raw.strip_suffix('\r') {
+                Some(line) => {
+                    issues.push(Issue::new(index, line.len(), IssueKind::Crlf));
+                    line
+                }
+                None => raw,
+            };
+            if is_comment(line) {
+                continue;
+            }
+
+            let trimmed = line.trim_end_matches([' ', '\t']);
+            if trimmed.len() < line.len() {
+                issues.push(Issue::new(
+                    index,
+                    trimmed.len(),
+                    IssueKind::TrailingWhitespace,
+                ));
+            }
+            issues.extend(
+                find_mixed_whitespace(trimmed)
+                    .into_iter()
+                    .map(|byte| Issue::new(index, byte, IssueKind::MixedWhitespace)),
+            );
+        }
+
+        // Rows are the lines other than comments, in order.
+        let rows: Vec<(usize, &str)> = lines
+            .iter()
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .enumerate()
+            .filter(|(_, line)| !is_comment(line))
+            .map(|(index, line)| (index, line.trim_end_matches([' ', '\t'])))
+            .collect();
+        let text = rows
+            .iter()
+            .map(|(_, line)| *line)
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let tab_size = options.tab_size.max(1);
+
+        // Misaligned text is what is looked for, so the layout is not detected from it.
+        if options.layout.unwrap_or(Layout::Aligned) == Layout::Aligned && text.contains('\t') {
+            let sheet = Self::parse_aligned(&text, tab_size);
+            for (row, &(index, line)) in rows.iter().enumerate() {
+                issues.extend(sheet.lint_row(row, index, line));
+            }
+            issues.sort_by_key(|issue| (issue.line, issue.byte));
+        }
+
+        issues
+    }
+
+    /// Checks the units in the row of `row`, read from `line`, the line of `index`, against the
+    /// columns they were read into, and the tabs before them against those the columns call
+    /// for.
+    fn lint_row(&self, row: usize, index: usize, line: &str) -> Vec<Issue> {
+        let mut issues = vec![];
+
+        let actual = split_items(line);
+        let canonical = self.format_row(row, Layout::Aligned);
+        let expected = split_items(&canonical);
+        // The units of the line are those of the row, in order.
+        let cols = (0..self.size.0).filter(|&col| self.units.contains_key(&(col, row)));
+
+        for ((item, expected), col) in actual.iter().zip(&expected).zip(cols) {
+            if item.tabs != expected.tabs {
+                issues.push(Issue::new(
+                    index,
+                    item.byte,
+                    IssueKind::Padding {
+                        expected: expected.tabs,
+                        found: item.tabs,
+                    },
+                ));
+            }
+
+            let width = Self::measure_width(item.content, self.tab_size);
+            if width > self.widths[col] {
+                issues.push(Issue::new(
+                    index,
+                    item.byte + item.tabs,
+                    IssueKind::TooWide {
+                        width,
+                        col_width: self.widths[col],
+                    },
+                ));
+            }
+        }
+
+        issues
+    }
+}
+
+impl Issue {
+    fn new(line: usize, byte: usize, kind: IssueKind) -> Self {
+        Self { line, byte, kind }
+    }
+}
+
+/// Splits a line into its units and the runs of tabs before them.
+fn split_items(line: &str) -> Vec<Item<'_>> {
+    let mut items = vec![];
+    let mut byte = 0;
+    let mut tabs = 0;
+
+    for (index, piece) in line.split('\t').enumerate() {
+        if index > 0 {
+            tabs += 1;
+        }
+        if !piece.is_empty() {
+            items.push(Item {
+                content: piece,
+                byte: byte - tabs,
+                tabs,
+            });
+            tabs = 0;
+        }
+        byte += piece.len() + 1;
+    }
+
+    items
+}
+
+fn count_tabs(n: usize) -> String {
+    match n {
+        1 => "1 tab".to_owned(),
+        n => format!("{} tabs", n),
+    }
+}
+
+/// Finds the runs of whitespace mixing spaces and tabs in a line. Returns their offsets in
+/// bytes.
+fn find_mixed_whitespace(line: &str) -> Vec<usize> {
+    let mut offsets = vec![];
+    let mut run: Option<(usize, bool, bool)> = None;
+
+    for (byte, c) in line.char_indices().chain([(line.len(), '\0')]) {
+        match (c, &mut run) {
+            (' ', Some((_, spaces, _))) => *spaces = true,
+            ('\t', Some((_, _, tabs))) => *tabs = true,
+            (' ', None) => run = Some((byte, true, false)),
+            ('\t', None) => run = Some((byte, false, true)),
+            _ => {
+                if let Some((start, true, true)) = run {
+                    offsets.push(start);
+                }
+                run = None;
+            }
+        }
+    }
+
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(buf: &str, layout: Option<Layout>) -> Vec<String> {
+        let options = ParseOptions {
+            layout,
+            ..ParseOptions::default()
+        };
+        Sheet::lint(buf, &options)
+            .iter()
+            .map(Issue::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn clean() {
+        let buf = "name\tprice\napple\t3\n";
+        assert!(lint(buf, None).is_empty());
+        assert!(lint(buf, Some(Layout::Aligned)).is_empty());
+    }
+
+    #[test]
+    fn padded() {
+        // Columns as wide as their gaps, rather than their units, are not misaligned.
+        let buf = "a\t\t\tb\nc\t\t\td\n";
+        assert!(lint(buf, None).is_empty());
+        assert!(lint(buf, Some(Layout::Aligned)).is_empty());
+        assert!(lint("name\t\tprice\nlonger name\t3\n", None).is_empty());
+    }
+
+    #[test]
+    fn elastic() {
+        let buf = "name\tprice\nlongername\t3\n";
+        assert!(lint(buf, Some(Layout::Elastic)).is_empty());
+        assert!(lint(buf, Some(Layout::Spaces)).is_empty());
+        for layout in [None, Some(Layout::Aligned)] {
+            assert_eq!(
+                lint(buf, layout),
+                ["2:1: unit is 2 tab stops wide, wider than its column of 1"]
+            );
+        }
+    }
+
+    #[test]
+    fn crlf() {
+        assert_eq!(
+            lint("a\tb\r\nc\td\r\n", None),
+            ["1:4: line ends with CRLF", "2:4: line ends with CRLF"]
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        assert_eq!(
+            lint("a\tb \nc\td\t\n", None),
+            ["1:4: trailing whitespace", "2:4: trailing whitespace"]
+        );
+    }
+
+    #[test]
+    fn mixed_whitespace() {
+        assert_eq!(lint("a \tb\nc\td\n", None), ["1:2: spaces and tabs mixed"]);
+        assert_eq!(lint("a  b\nc \t d\n", None), ["2:2: spaces and tabs mixed"]);
+    }
+}
@@ -0,0 +1,77 @@
+//! Merging changes made to two copies of a [`Sheet`].
+
+use std::collections::BTreeSet;
+
+use super::Sheet;
+
+impl Sheet {
+    /// Merges into the sheet the changes `theirs` made to `base`, which the sheet was also
+    /// changed from. Units are matched by their positions. Where both changed a unit, but not
+    /// in the same way, the sheet keeps its own content.
+    ///
+    /// Returns the positions of such conflicts, row by row. Represented in `(col, row)`.
+    pub fn merge(&mut self, base: &Sheet, theirs: &Sheet) -> Vec<(usize, usize)> {
+        let positions: BTreeSet<(usize, usize)> = [base, &*self, theirs]
+            .iter()
+            .flat_map(|sheet| sheet.units.keys().map(|pos| (pos.1, pos.0)))
+            .collect();
+
+        let mut changes = vec![];
+        let mut conflicts = vec![];
+        for (row, col) in positions {
+            let pos = (col, row);
+            let (base_content, ours, their_content) = (
+                base.content_at(pos),
+                self.content_at(pos),
+                theirs.content_at(pos),
+            );
+
+            if their_content == base_content || their_content == ours {
+                continue;
+            }
+            if ours == base_content {
+                changes.push((pos, their_content.unwrap_or_default().to_owned()));
+            } else {
+                conflicts.push(pos);
+            }
+        }
+
+        for (pos, content) in changes {
+            self.set(pos, &content);
+        }
+        if self.comments == base.comments {
+            self.comments = theirs.comments.clone();
+        }
+
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_their_changes() {
+        let base = Sheet::from_str("a\tb\nc\td\n");
+        let mut ours = Sheet::from_str("a\tB\nc\td\n");
+        let theirs = Sheet::from_str("a\tb\nC\td\ne\n");
+
+        assert_eq!(ours.merge(&base, &theirs), []);
+        assert_eq!(ours.content_at((1, 0)), Some("B"));
+        assert_eq!(ours.content_at((0, 1)), Some("C"));
+        assert_eq!(ours.content_at((0, 2)), Some("e"));
+    }
+
+    #[test]
+    fn conflicts_keep_ours() {
+        let base = Sheet::from_str("a\tb\n");
+        let mut ours = Sheet::from_str("x\tb\ny\n");
+        let theirs = Sheet::from_str("z\t\ny\n");
+
+        assert_eq!(ours.merge(&base, &theirs), [(0, 0)]);
+        assert_eq!(ours.content_at((0, 0)), Some("x"));
+        assert_eq!(ours.content_at((1, 0)), None);
+        assert_eq!(ours.content_at((0, 1)), Some("y"));
+    }
+}
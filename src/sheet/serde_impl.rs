@@ -0,0 +1,327 @@
+//! `Serialize` and `Deserialize` support for [`Sheet`], enabled by the `serde` feature.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use serde::{
+    de::{self, value::Error, DeserializeOwned, IntoDeserializer, Visitor},
+    forward_to_deserialize_any,
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::{Layout, Sheet, Unit};
+use crate::value::CellType;
+
+/// A sheet is serialized as its size, its layout, a list of `(col, row, content)` triples
+/// ordered by row, and the declared types of columns. Column widths are not stored, but
+/// measured again when deserializing, and empty rows and columns at the end are not kept.
+impl Serialize for Sheet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut units: Vec<(usize, usize, &Unit)> = self
+            .units
+            .iter()
+            .map(|(pos, unit)| (pos.0, pos.1, unit))
+            .collect();
+        units.sort_by_key(|u| (u.1, u.0));
+
+        let mut column_types: Vec<(usize, CellType)> = self
+            .column_types
+            .iter()
+            .map(|(&col, &ty)| (col, ty))
+            .collect();
+        column_types.sort_by_key(|t| t.0);
+
+        let mut state = serializer.serialize_struct("Sheet", 4)?;
+        state.serialize_field("size", &get_bounds(self.units.keys()))?;
+        state.serialize_field("layout", &self.layout)?;
+        state.serialize_field("units", &units)?;
+        state.serialize_field("column_types", &column_types)?;
+        state.end()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Sheet")]
+struct SheetData {
+    size: (usize, usize),
+    #[serde(default)]
+    layout: Layout,
+    units: Vec<(usize, usize, Unit)>,
+    #[serde(default)]
+    column_types: Vec<(usize, CellType)>,
+}
+
+/// Most columns and rows a deserialized sheet may have, as many as in common spreadsheets.
+const MAX_SIZE: (usize, usize) = (16_384, 1_048_576);
+
+/// As sizes and positions may come from untrusted input, the size must be that of the smallest
+/// sheet holding every unit, at least 1 by 1, rather than trusted to allocate widths. Sheets
+/// larger than [`MAX_SIZE`] are rejected.
+impl<'de> Deserialize<'de> for Sheet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = SheetData::deserialize(deserializer)?;
+
+        let units: HashMap<(usize, usize), Unit> = data
+            .units
+            .into_iter()
+            .filter(|u| !u.2.content.is_empty())
+            .map(|(col, row, unit)| ((col, row), unit))
+            .collect();
+
+        let bounds = get_bounds(units.keys())
+            .filter(|bounds| bounds.0 <= MAX_SIZE.0 && bounds.1 <= MAX_SIZE.1)
+            .ok_or_else(|| {
+                de::Error::custom(format_args!(
+                    "position of a unit out of range, beyond {} columns or {} rows",
+                    MAX_SIZE.0, MAX_SIZE.1
+                ))
+            })?;
+        if data.size != bounds {
+            return Err(de::Error::custom(format_args!(
+                "size {:?} does not fit the units, which take up {:?}",
+                data.size, bounds
+            )));
+        }
+
+        let mut sheet = Sheet::from_units(units, data.size);
+        sheet.layout = data.layout;
+        for (col, ty) in data.column_types {
+            sheet.set_column_type(col, Some(ty));
+        }
+
+        Ok(sheet)
+    }
+}
+
+/// Gets the size of the smallest sheet holding units at `positions`, at least 1 by 1. Returns
+/// `None` if it would overflow.
+fn get_bounds<'a>(
+    mut positions: impl Iterator<Item = &'a (usize, usize)>,
+) -> Option<(usize, usize)> {
+    positions.try_fold((1, 1), |size, pos| {
+        Some((
+            size.0.max(pos.0.checked_add(1)?),
+            size.1.max(pos.1.checked_add(1)?),
+        ))
+    })
+}
+
+impl Sheet {
+    /// Deserializes every non-empty row below the first one into `T`, using the contents of the
+    /// first row as field names.
+    ///
+    /// Numbers and booleans are parsed from the contents of units, and empty units are read as
+    /// `None` by optional fields.
+    pub fn deserialize_rows<T: DeserializeOwned>(&self) -> Result<Vec<T>, Error> {
+        let headers = self.headers();
+
+        (1..self.size.1)
+            .filter(|&row| !self.is_row_empty(row))
+            .map(|row| {
+                let fields = headers.iter().map(|&(col, name)| {
+                    (
+                        name,
+                        UnitDeserializer {
+                            name,
+                            content: self.content_at((col, row)),
+                        },
+                    )
+                });
+
+                T::deserialize(de::value::MapDeserializer::new(fields))
+                    .map_err(|err| de::Error::custom(format_args!("row {}: {}", row + 1, err)))
+            })
+            .collect()
+    }
+}
+
+/// Deserializes the content of a single unit, parsing it into the type being asked for.
+struct UnitDeserializer<'a> {
+    /// Name of the field being deserialized, used in error messages.
+    name: &'a str,
+    content: Option<&'a str>,
+}
+
+impl UnitDeserializer<'_> {
+    fn parse<T>(&self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let s = self.content.unwrap_or_default();
+        s.trim().parse().map_err(|err| {
+            de::Error::custom(format_args!(
+                "invalid value {:?} for `{}`: {}",
+                s, self.name, err
+            ))
+        })
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for UnitDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for UnitDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.content {
+            Some(s) => visitor.visit_borrowed_str(s),
+            None => visitor.visit_none(),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.content.unwrap_or_default())
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.content {
+            Some(s) if !s.trim().is_empty() => visitor.visit_some(self),
+            _ => visitor.visit_none(),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.content.unwrap_or_default().into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut sheet = Sheet::from_str("a\tb\n\tc\n");
+        sheet.set_column_type(1, Some(CellType::Integer));
+
+        let json = serde_json::to_string(&sheet).unwrap();
+        let read: Sheet = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.size(), (2, 2));
+        assert_eq!(read.content_at((1, 1)), Some("c"));
+        assert_eq!(read.column_type(1), Some(CellType::Integer));
+    }
+
+    #[test]
+    fn empty_sheet() {
+        let json = serde_json::to_string(&Sheet::new()).unwrap();
+        let read: Sheet = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.size(), (1, 1));
+    }
+
+    #[test]
+    fn size_must_fit_units() {
+        let too_small = r#"{"size":[1,1],"units":[[1,0,"a"]]}"#;
+        assert!(serde_json::from_str::<Sheet>(too_small).is_err());
+
+        let too_large = r#"{"size":[18446744073709551615,1],"units":[[0,0,"a"]]}"#;
+        assert!(serde_json::from_str::<Sheet>(too_large).is_err());
+    }
+
+    #[test]
+    fn position_out_of_range() {
+        let json = r#"{"size":[1,1],"units":[[18446744073709551615,0,"a"]]}"#;
+        assert!(serde_json::from_str::<Sheet>(json).is_err());
+
+        // Sizes fitting the units are still too large to allocate widths for.
+        let json = r#"{"size":[18446744073709551615,1],"units":[[18446744073709551614,0,"a"]]}"#;
+        assert!(serde_json::from_str::<Sheet>(json).is_err());
+        let json = r#"{"size":[1000000001,1],"units":[[1000000000,0,"a"]]}"#;
+        assert!(serde_json::from_str::<Sheet>(json).is_err());
+        let json = r#"{"size":[1,2000000],"units":[[0,1999999,"a"]]}"#;
+        assert!(serde_json::from_str::<Sheet>(json).is_err());
+
+        let json = r#"{"size":[16384,1],"units":[[16383,0,"a"]]}"#;
+        let read: Sheet = serde_json::from_str(json).unwrap();
+        assert_eq!(read.content_at((16383, 0)), Some("a"));
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Item {
+        name: String,
+        price: f64,
+        stock: Option<u32>,
+    }
+
+    #[test]
+    fn deserialize_rows() {
+        let sheet = Sheet::from_str("name\tprice\tstock\napple\t1.5\t3\n\npear\t2\n");
+        let items: Vec<Item> = sheet.deserialize_rows().unwrap();
+        assert_eq!(
+            items,
+            [
+                Item {
+                    name: "apple".to_owned(),
+                    price: 1.5,
+                    stock: Some(3),
+                },
+                Item {
+                    name: "pear".to_owned(),
+                    price: 2.0,
+                    stock: None,
+                },
+            ]
+        );
+
+        let sheet = Sheet::from_str("name\tstock\napple\t3\n");
+        let err = sheet.deserialize_rows::<Item>().unwrap_err();
+        assert!(err.to_string().contains("row 2"), "{}", err);
+        assert!(err.to_string().contains("price"), "{}", err);
+
+        let sheet = Sheet::from_str("name\tprice\napple\tcheap\n");
+        let err = sheet.deserialize_rows::<Item>().unwrap_err();
+        assert!(err.to_string().contains("`price`"), "{}", err);
+    }
+}
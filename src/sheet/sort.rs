@@ -0,0 +1,316 @@
+//! Sorting the rows of a [`Sheet`].
+
+use std::{cmp::Ordering, collections::HashMap, ops::Range};
+
+use super::{Sheet, Unit};
+use crate::{formula::reorder_row_references, value::CellValue};
+
+/// A column to sort rows by, and how to compare its units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub col: usize,
+    pub order: SortOrder,
+    pub collation: Collation,
+}
+
+impl SortKey {
+    /// Sorts by the column of `col` in ascending, lexical order.
+    pub fn new(col: usize) -> Self {
+        Self {
+            col,
+            order: SortOrder::Ascending,
+            collation: Collation::Lexical,
+        }
+    }
+
+    pub fn descending(mut self) -> Self {
+        self.order = SortOrder::Descending;
+        self
+    }
+
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    /// Compares two units. Empty units always come last, and so do units of later kinds, such
+    /// as contents that are not numbers in [`Collation::Numeric`], whatever the order.
+    fn compare(&self, a: Option<&SortItem>, b: Option<&SortItem>) -> Ordering {
+        match (a, b) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => {
+                let kinds = self.collation.kind_of(a).cmp(&self.collation.kind_of(b));
+                kinds.then_with(|| {
+                    let ordering = self.collation.compare_units(a, b);
+                    match self.order {
+                        SortOrder::Ascending => ordering,
+                        SortOrder::Descending => ordering.reverse(),
+                    }
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// How the contents of two units are compared.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+    /// By their characters.
+    #[default]
+    Lexical,
+    /// By their values as numbers, percentages included. Contents that are not numbers come
+    /// after those that are.
+    Numeric,
+    /// By their characters, except that runs of digits are compared by their values, so that
+    /// `item2` comes before `item10`.
+    Natural,
+    /// By their typed values, as in [`CellValue::compare`](crate::value::CellValue::compare).
+    Typed,
+}
+
+impl Collation {
+    /// Compares two contents, inferring their types if needed.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        let (a, b) = (Unit::from(a), Unit::from(b));
+        self.compare_units(&self.item(&a), &self.item(&b))
+    }
+
+    /// Gets what is compared of a unit, parsing its value if needed.
+    fn item<'a>(&self, unit: &'a Unit) -> SortItem<'a> {
+        SortItem {
+            content: &unit.content,
+            value: matches!(self, Collation::Numeric | Collation::Typed).then(|| unit.value()),
+        }
+    }
+
+    /// Ranks the kind of a unit, such that units of a lower rank come first in either order.
+    fn kind_of(&self, item: &SortItem) -> u8 {
+        match self {
+            Collation::Numeric => u8::from(item.number().is_none()),
+            Collation::Typed => item.value.as_ref().map_or(0, CellValue::rank),
+            Collation::Lexical | Collation::Natural => 0,
+        }
+    }
+
+    fn compare_units(&self, a: &SortItem, b: &SortItem) -> Ordering {
+        match self {
+            Collation::Lexical => a.content.cmp(b.content),
+            Collation::Numeric => match (a.number(), b.number()) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => a.content.cmp(b.content),
+            },
+            Collation::Natural => compare_natural(a.content, b.content),
+            Collation::Typed => match (&a.value, &b.value) {
+                (Some(x), Some(y)) => x.compare(y),
+                _ => a.content.cmp(b.content),
+            },
+        }
+    }
+}
+
+/// What is compared of a unit: its content, and its value if the collation compares values.
+/// Made once for each unit before sorting, rather than for every comparison.
+struct SortItem<'a> {
+    content: &'a str,
+    value: Option<CellValue>,
+}
+
+impl SortItem<'_> {
+    fn number(&self) -> Option<f64> {
+        self.value.as_ref().and_then(CellValue::as_number)
+    }
+}
+
+/// Compares two string slices, with runs of digits compared by their values.
+fn compare_natural(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut digits_a = String::new();
+                while let Some(c) = a.next_if(char::is_ascii_digit) {
+                    digits_a.push(c);
+                }
+                let mut digits_b = String::new();
+                while let Some(c) = b.next_if(char::is_ascii_digit) {
+                    digits_b.push(c);
+                }
+
+                let trimmed_a = digits_a.trim_start_matches('0');
+                let trimmed_b = digits_b.trim_start_matches('0');
+                let ordering = trimmed_a
+                    .len()
+                    .cmp(&trimmed_b.len())
+                    .then_with(|| trimmed_a.cmp(trimmed_b))
+                    .then_with(|| digits_a.len().cmp(&digits_b.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+impl Sheet {
+    /// Sorts all rows by `keys`, in order of precedence. The first row is left in place if
+    /// `keep_header` is set. Rows that compare equal keep their order.
+    pub fn sort_rows_by(&mut self, keys: &[SortKey], keep_header: bool) {
+        let start = usize::from(keep_header);
+        self.sort_rows_in(start..self.size.1, keys);
+    }
+
+    /// Sorts the rows in `rows` by `keys`, in order of precedence, leaving the others in place.
+    pub fn sort_rows_in(&mut self, rows: Range<usize>, keys: &[SortKey]) {
+        let rows = rows.start..rows.end.min(self.size.1);
+        if rows.len() < 2 {
+            return;
+        }
+
+        let order: Vec<usize> = {
+            let items: Vec<Vec<Option<SortItem>>> = rows
+                .clone()
+                .map(|row| {
+                    keys.iter()
+                        .map(|key| self.unit_at((key.col, row)).map(|u| key.collation.item(u)))
+                        .collect()
+                })
+                .collect();
+
+            let mut order: Vec<usize> = (0..rows.len()).collect();
+            order.sort_by(|&a, &b| {
+                keys.iter()
+                    .zip(items[a].iter().zip(&items[b]))
+                    .map(|(key, (x, y))| key.compare(x.as_ref(), y.as_ref()))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+            order.into_iter().map(|i| rows.start + i).collect()
+        };
+
+        let mut moved: HashMap<(usize, usize), Unit> = HashMap::new();
+        let mut new_rows = vec![0; rows.len()];
+        for (new_row, &old_row) in rows.clone().zip(order.iter()) {
+            for col in 0..self.size.0 {
+                if let Some(unit) = self.units.remove(&(col, old_row)) {
+                    moved.insert((col, new_row), unit);
+                }
+            }
+            new_rows[old_row - rows.start] = new_row;
+        }
+        self.units.extend(moved);
+
+        // Comments before the sorted rows move along with them, in the same order.
+        for comment in &mut self.comments {
+            if rows.contains(&comment.row) {
+                comment.row = new_rows[comment.row - rows.start];
+            }
+        }
+        self.comments.sort_by_key(|comment| comment.row);
+
+        // Formulas refer to the units they did, wherever those moved.
+        self.rewrite_formulas(|content| reorder_row_references(content, &rows, &new_rows));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sheet::ParseOptions;
+
+    fn column(sheet: &Sheet) -> Vec<&str> {
+        (0..sheet.size().1)
+            .map(|row| sheet.content_at((0, row)).unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn numeric_keeps_numbers_first() {
+        let mut sheet = Sheet::from_str("b\n10\n\n2\na\n");
+        let key = SortKey::new(0).collation(Collation::Numeric);
+
+        sheet.sort_rows_by(&[key], false);
+        assert_eq!(column(&sheet), ["2", "10", "a", "b", ""]);
+        sheet.sort_rows_by(&[key.descending()], false);
+        assert_eq!(column(&sheet), ["10", "2", "b", "a", ""]);
+    }
+
+    #[test]
+    fn typed_keeps_kinds_in_order() {
+        let mut sheet = Sheet::from_str("x\n2023-04-01\n3\ntrue\n1\n");
+        let key = SortKey::new(0).collation(Collation::Typed).descending();
+
+        sheet.sort_rows_by(&[key], false);
+        assert_eq!(column(&sheet), ["3", "1", "2023-04-01", "true", "x"]);
+    }
+
+    #[test]
+    fn natural() {
+        assert_eq!(compare_natural("item2", "item10"), Ordering::Less);
+        assert_eq!(compare_natural("item02", "item2"), Ordering::Greater);
+        assert_eq!(compare_natural("a", "a1"), Ordering::Less);
+    }
+
+    #[test]
+    fn stable_with_header() {
+        let mut sheet = Sheet::from_str("name\tn\nb\t1\na\t1\nc\t0\n");
+        sheet.sort_rows_by(&[SortKey::new(1)], true);
+        assert_eq!(column(&sheet), ["name", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn range_only() {
+        let mut sheet = Sheet::from_str("d\nc\nb\na\n");
+        sheet.sort_rows_in(1..3, &[SortKey::new(0)]);
+        assert_eq!(column(&sheet), ["d", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn formulas_follow_rows() {
+        let mut sheet =
+            Sheet::from_str("n\tx\ty\n3\t2\t=B2*A2\n1\t5\t=B3+SUM(A2:A4)\n2\t=A4\t=C2\n");
+        sheet.sort_rows_by(&[SortKey::new(0)], true);
+        assert_eq!(column(&sheet), ["n", "1", "2", "3"]);
+        assert_eq!(sheet.content_at((2, 1)), Some("=B2+SUM(A2:A4)"));
+        assert_eq!(sheet.content_at((1, 2)), Some("=A3"));
+        assert_eq!(sheet.content_at((2, 2)), Some("=C4"));
+        assert_eq!(sheet.content_at((2, 3)), Some("=B4*A4"));
+    }
+
+    #[test]
+    fn comments_move_with_rows() {
+        let options = ParseOptions {
+            comment_prefix: Some("#".to_owned()),
+            ..ParseOptions::default()
+        };
+        let mut sheet = Sheet::parse_with("n\n# three\n3\n1\n# two\n2\n# end\n", &options);
+        sheet.sort_rows_by(&[SortKey::new(0)], true);
+        assert_eq!(
+            sheet.comments().iter().map(|c| c.row).collect::<Vec<_>>(),
+            [2, 3, 4]
+        );
+        assert_eq!(column(&sheet), ["n", "1", "2", "3"]);
+    }
+}
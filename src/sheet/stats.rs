@@ -0,0 +1,174 @@
+//! Summarizing the values in a [`RangeView`] of a sheet.
+
+use std::collections::HashMap;
+
+use super::RangeView;
+use crate::value::CellType;
+
+/// Statistics of the numbers in a range, percentages counting as fractions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub count: usize,
+    pub sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Number of different values.
+    pub distinct: usize,
+}
+
+impl Stats {
+    /// Computes the statistics of given numbers.
+    pub fn from_numbers(numbers: impl IntoIterator<Item = f64>) -> Self {
+        let mut numbers: Vec<f64> = numbers.into_iter().collect();
+        numbers.sort_by(f64::total_cmp);
+
+        let mut stats = Stats {
+            count: numbers.len(),
+            sum: numbers.iter().sum(),
+            min: numbers.first().copied(),
+            max: numbers.last().copied(),
+            distinct: 0,
+        };
+        numbers.dedup();
+        stats.distinct = numbers.len();
+
+        stats
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// A fuller summary of the units in a range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    /// Number of non-empty units.
+    pub units: usize,
+    /// Number of empty positions.
+    pub empty: usize,
+    /// Number of units of each type, the most common first.
+    pub types: Vec<(CellType, usize)>,
+    /// The most common contents with their numbers of occurrences, the most common first.
+    pub top_values: Vec<(String, usize)>,
+    pub stats: Stats,
+}
+
+impl RangeView<'_> {
+    /// Computes the statistics of the numbers in the view. Formulas are not evaluated, so they
+    /// are not counted; see [`RangeView::stats_with`].
+    pub fn stats(&self) -> Stats {
+        self.stats_with(|_| true, |pos| self.sheet.value_at(pos)?.as_number())
+    }
+
+    /// Computes the statistics of the numbers in the rows of the view for which `visible`
+    /// holds, each read by `number` from its position, e.g. with formulas evaluated.
+    pub fn stats_with(
+        &self,
+        visible: impl Fn(usize) -> bool,
+        number: impl Fn((usize, usize)) -> Option<f64>,
+    ) -> Stats {
+        Stats::from_numbers(
+            self.cells()
+                .filter(|(pos, _)| visible(pos.1))
+                .filter_map(|(pos, _)| number(pos)),
+        )
+    }
+
+    /// Summarizes the units in the view, keeping up to `top` of the most common contents.
+    /// Formulas are counted by their contents; see [`RangeView::summary_with`].
+    pub fn summary(&self, top: usize) -> Summary {
+        self.summary_with(top, |_| true, |pos| self.sheet.value_at(pos)?.as_number())
+    }
+
+    /// Summarizes the units in the rows of the view for which `visible` holds, the same as
+    /// [`RangeView::summary`] does, reading numbers as [`RangeView::stats_with`] does.
+    pub fn summary_with(
+        &self,
+        top: usize,
+        visible: impl Fn(usize) -> bool,
+        number: impl Fn((usize, usize)) -> Option<f64>,
+    ) -> Summary {
+        let mut types: HashMap<CellType, usize> = HashMap::new();
+        let mut contents: HashMap<&str, usize> = HashMap::new();
+        let mut units = 0;
+        for (pos, content) in self.cells().filter(|(pos, _)| visible(pos.1)) {
+            units += 1;
+            if let Some(value) = self.sheet.value_at(pos) {
+                *types.entry(value.ty()).or_default() += 1;
+            }
+            *contents.entry(content).or_default() += 1;
+        }
+
+        // Only the units inside the sheet are counted as empty.
+        let area = match self.clipped_end() {
+            Some(end) => {
+                let rows = (self.start().1..=end.1).filter(|&row| visible(row)).count();
+                (end.0 - self.start().0 + 1) * rows
+            }
+            None => 0,
+        };
+
+        let mut types: Vec<(CellType, usize)> = types.into_iter().collect();
+        types.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then_with(|| a.0.to_string().cmp(&b.0.to_string()))
+        });
+        let mut top_values: Vec<(String, usize)> = contents
+            .into_iter()
+            .map(|(content, n)| (content.to_owned(), n))
+            .collect();
+        top_values.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_values.truncate(top);
+
+        Summary {
+            units,
+            empty: area - units,
+            types,
+            top_values,
+            stats: self.stats_with(visible, number),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sheet::Sheet;
+
+    #[test]
+    fn stats() {
+        let sheet = Sheet::from_str("n\n3\n1\n50%\nx\n3\n");
+        let stats = sheet.range((0, 0)..=(0, 5)).stats();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.sum, 7.5);
+        assert_eq!((stats.min, stats.max), (Some(0.5), Some(3.0)));
+        assert_eq!(stats.distinct, 3);
+        assert_eq!(stats.mean(), Some(1.875));
+    }
+
+    #[test]
+    fn visible_rows_and_numbers() {
+        let sheet = Sheet::from_str("1\n=A1+1\n5\n");
+        let range = sheet.range((0, 0)..=(0, 3));
+        assert_eq!(range.stats().sum, 6.0);
+
+        let summary = range.summary_with(
+            5,
+            |row| row != 2,
+            |pos| match sheet.content_at(pos)? {
+                "=A1+1" => Some(2.0),
+                content => content.parse().ok(),
+            },
+        );
+        assert_eq!(summary.units, 2);
+        assert_eq!(summary.empty, 0);
+        assert_eq!(summary.stats.sum, 3.0);
+
+        let summary = sheet.range((0, 0)..=(usize::MAX, usize::MAX)).summary(5);
+        assert_eq!(summary.units, 3);
+        assert_eq!(summary.empty, 0);
+        let sheet = Sheet::from_str("1\ta\n2\n");
+        let summary = sheet.range((0, 0)..=(usize::MAX, usize::MAX)).summary(5);
+        assert_eq!((summary.units, summary.empty), (3, 1));
+    }
+}
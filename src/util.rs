@@ -1,6 +1,10 @@
 use std::{
-    io::{self, stdout, Write},
+    fs::{self, File},
+    io::{self, stdin, stdout, BufRead, BufReader, IsTerminal, Write},
     ops::Add,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
 };
 
 use crossterm::{
@@ -10,6 +14,7 @@ use crossterm::{
     style::Print,
     terminal::{Clear, ClearType},
 };
+use unicode_width::UnicodeWidthChar;
 
 /// Check if given `val` lies in `lbd..lbd + ofs`.
 pub fn is_in_offset_bounds<T>(val: T, lbd: T, ofs: T) -> bool
@@ -19,7 +24,44 @@ where
     lbd <= val && val < lbd + ofs
 }
 
-pub fn read_line_initial_text(initial: &str) -> io::Result<String> {
+/// Cuts given string slice to at most `width` columns wide when displayed.
+pub fn truncate_to_width(s: &str, width: usize) -> &str {
+    let mut total = 0;
+    for (i, c) in s.char_indices() {
+        total += c.width().unwrap_or(0);
+        if total > width {
+            return &s[..i];
+        }
+    }
+
+    s
+}
+
+/// Path of the terminal, to read from when stdin is not one.
+#[cfg(windows)]
+const TERMINAL_PATH: &str = "CONIN$";
+#[cfg(not(windows))]
+const TERMINAL_PATH: &str = "/dev/tty";
+
+/// Reads a line typed in the terminal, even if stdin is piped from elsewhere.
+pub fn read_line_from_terminal() -> io::Result<String> {
+    let mut buf = String::new();
+    if stdin().is_terminal() {
+        stdin().read_line(&mut buf)?;
+    } else {
+        BufReader::new(File::open(TERMINAL_PATH)?).read_line(&mut buf)?;
+    }
+
+    Ok(buf)
+}
+
+/// Reads a line typed in raw mode, following `initial`. While waiting for keys, `idle` is
+/// called with when the last one was pressed, to do what is due by then. It returns how long to
+/// wait for the next key before it is called again, if at all.
+pub fn read_line_initial_text(
+    initial: &str,
+    mut idle: impl FnMut(Instant) -> io::Result<Option<Duration>>,
+) -> io::Result<String> {
     if initial.is_empty() {
         execute!(stdout(), Clear(ClearType::UntilNewLine))?;
     } else {
@@ -32,17 +74,26 @@ pub fn read_line_initial_text(initial: &str) -> io::Result<String> {
     }
 
     let mut chars: Vec<char> = initial.chars().collect();
+    let mut last_key = Instant::now();
 
     loop {
+        if let Some(timeout) = idle(last_key)? {
+            if !event::poll(timeout)? {
+                continue;
+            }
+        }
+
         if let Event::Key(event) = event::read()? {
+            last_key = Instant::now();
             match event {
                 KeyEvent {
                     code: KeyCode::Backspace,
                     ..
                 } => {
-                    if chars.pop().is_some() {
-                        execute!(stdout(), MoveLeft(1), Clear(ClearType::UntilNewLine))?;
+                    if chars.pop().is_none() {
+                        continue;
                     }
+                    execute!(stdout(), MoveLeft(1), Clear(ClearType::UntilNewLine))?;
                 }
                 KeyEvent {
                     code: KeyCode::Char(c),
@@ -68,3 +119,240 @@ pub fn read_line_initial_text(initial: &str) -> io::Result<String> {
 
     Ok(chars.iter().collect())
 }
+
+/// Checks if the process of `pid` is running. Returns `None` if that cannot be told.
+#[cfg(unix)]
+pub fn is_process_running(pid: u32) -> Option<bool> {
+    if Path::new("/proc/self").exists() {
+        return Some(Path::new("/proc").join(pid.to_string()).exists());
+    }
+
+    let status = process::Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .stderr(process::Stdio::null())
+        .status()
+        .ok()?;
+    Some(status.success())
+}
+
+/// Checks if the process of `pid` is running. Returns `None` if that cannot be told.
+#[cfg(not(unix))]
+pub fn is_process_running(_pid: u32) -> Option<bool> {
+    None
+}
+
+/// Checks if the file at `path` can be written to, by opening it for writing without changing
+/// it. Files that do not exist yet count as writable. As [`write_atomically`] replaces files, the
+/// directory holding it must be writable too, which is checked by creating a temporary file in
+/// it.
+pub fn is_writable(path: &Path) -> bool {
+    let writable = match File::options().write(true).open(path) {
+        Ok(_) => true,
+        Err(e) => e.kind() == io::ErrorKind::NotFound,
+    };
+    if !writable {
+        return false;
+    }
+
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    match create_temp(&path) {
+        Ok((_, temp_path)) => fs::remove_file(temp_path).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Creates a temporary file to be written in place of the file at `path`, in the same
+/// directory. Returns it and its path. Names already taken, such as by files left behind by a
+/// crashed process of the same ID, are skipped.
+fn create_temp(path: &Path) -> io::Result<(File, PathBuf)> {
+    const ATTEMPTS: usize = 100;
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut attempt = 0;
+    loop {
+        let temp_path = match attempt {
+            0 => format!(".{}.{}.tmp", name, process::id()),
+            n => format!(".{}.{}.{}.tmp", name, process::id(), n),
+        };
+        let temp_path = parent_dir(path).join(temp_path);
+
+        match File::options()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((file, temp_path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt + 1 < ATTEMPTS => {
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Gets the directory holding the file at `path`.
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Replaces the file at `path` with what `write` writes, so that the file is either left as it
+/// was or entirely replaced, even if writing fails midway or the system crashes.
+///
+/// The content is written to a temporary file in the same directory, synced to disk, and
+/// renamed over the original, keeping its permissions. If `backup` is set, the original is
+/// first copied to the same path with a `~` appended.
+pub fn write_atomically(
+    path: &Path,
+    backup: bool,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> io::Result<()> {
+    // Replace what a symbolic link points to, rather than the link itself.
+    let path = match fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_owned(),
+        Err(e) => return Err(e),
+    };
+    let metadata = match fs::metadata(&path) {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let dir = parent_dir(&path);
+    let (file, temp_path) = create_temp(&path)?;
+
+    let result = (|| {
+        let mut file = file;
+        write(&mut file)?;
+        file.flush()?;
+        if let Some(metadata) = &metadata {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        // Closed before being renamed, which not every system allows while open.
+        drop(file);
+
+        if backup && metadata.is_some() {
+            let mut backup_path = path.clone().into_os_string();
+            backup_path.push("~");
+            fs::copy(&path, backup_path)?;
+        }
+
+        fs::rename(&temp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    // Make sure the rename itself reaches the disk.
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writable_needs_its_directory() {
+        let dir = std::env::temp_dir().join(format!("tab-o-txt-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("new.txt");
+        assert!(is_writable(&path));
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+
+        assert!(!is_writable(&dir.join("new.txt")));
+    }
+
+    #[test]
+    fn truncate_wide() {
+        assert_eq!(truncate_to_width("abc", 2), "ab");
+        assert_eq!(truncate_to_width("日本語", 5), "日本");
+        assert_eq!(truncate_to_width("ab", 5), "ab");
+    }
+
+    /// Makes an empty directory for a test, named after it.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tab-o-txt-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn failed_write_keeps_file() {
+        let dir = test_dir("failed-write");
+        let path = dir.join("a.txt");
+        fs::write(&path, "old\n").unwrap();
+
+        let result = write_atomically(&path, false, |file| {
+            file.write_all(b"half")?;
+            Err(io::Error::other("failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backup_keeps_old_contents() {
+        let dir = test_dir("backup");
+        let path = dir.join("a.txt");
+        fs::write(&path, "old\n").unwrap();
+
+        write_atomically(&path, true, |file| file.write_all(b"new\n")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(fs::read_to_string(dir.join("a.txt~")).unwrap(), "old\n");
+
+        // New files have nothing to back up.
+        let path = dir.join("b.txt");
+        write_atomically(&path, true, |file| file.write_all(b"b\n")).unwrap();
+        assert!(!dir.join("b.txt~").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permissions_kept() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("permissions");
+        let path = dir.join("a.txt");
+        fs::write(&path, "old\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_atomically(&path, false, |file| file.write_all(b"new\n")).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_temp_files_skipped() {
+        let dir = test_dir("stale-temp");
+        let path = dir.join("a.txt");
+        let stale = dir.join(format!(".a.txt.{}.tmp", process::id()));
+        fs::write(&stale, "stale").unwrap();
+
+        write_atomically(&path, false, |file| file.write_all(b"new\n")).unwrap();
+        write_atomically(&path, false, |file| file.write_all(b"newer\n")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "newer\n");
+        assert!(is_writable(&path));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
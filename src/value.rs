@@ -0,0 +1,378 @@
+//! Typed values of units.
+//!
+//! The type of a unit is inferred from its content, or declared for its column with
+//! [`Sheet::set_column_type`](crate::sheet::Sheet::set_column_type). Either way the content is
+//! kept as is, so that sheets are written back the way they were read.
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum CellType {
+    /// E.g. `-12`.
+    Integer,
+    /// E.g. `3.25`.
+    Decimal,
+    /// E.g. `12.5%`.
+    Percentage,
+    /// E.g. `2023-04-01`.
+    Date,
+    /// E.g. `13:45` or `13:45:30`.
+    Time,
+    /// E.g. `2023-04-01 13:45` or `2023-04-01T13:45:30`.
+    DateTime,
+    /// `true` or `false`, case-insensitively.
+    Bool,
+    Text,
+}
+
+impl CellType {
+    /// Infers the type of given content, trying the types in the order they are declared.
+    pub fn infer(content: &str) -> Self {
+        [
+            CellType::Integer,
+            CellType::Decimal,
+            CellType::Percentage,
+            CellType::Date,
+            CellType::Time,
+            CellType::DateTime,
+            CellType::Bool,
+        ]
+        .into_iter()
+        .find(|ty| ty.parse(content).is_some())
+        .unwrap_or(CellType::Text)
+    }
+
+    /// Parses given content as the type. Returns `None` if it is not of the type.
+    pub fn parse(&self, content: &str) -> Option<CellValue> {
+        let s = content.trim();
+
+        match self {
+            CellType::Integer => s.parse().ok().map(CellValue::Integer),
+            CellType::Decimal => s
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite() && s.bytes().any(|b| b.is_ascii_digit()))
+                .map(CellValue::Decimal),
+            CellType::Percentage => s
+                .strip_suffix('%')
+                .and_then(|n| n.trim_end().parse::<f64>().ok())
+                .filter(|n| n.is_finite())
+                .map(|n| CellValue::Percentage(n / 100.0)),
+            CellType::Date => s.parse().ok().map(CellValue::Date),
+            CellType::Time => s.parse().ok().map(CellValue::Time),
+            CellType::DateTime => {
+                let (date, time) = s.split_once(['T', ' '])?;
+                Some(CellValue::DateTime(
+                    date.parse().ok()?,
+                    time.trim_start().parse().ok()?,
+                ))
+            }
+            CellType::Bool => match s.to_ascii_lowercase().as_str() {
+                "true" => Some(CellValue::Bool(true)),
+                "false" => Some(CellValue::Bool(false)),
+                _ => None,
+            },
+            CellType::Text => Some(CellValue::Text(content.to_owned())),
+        }
+    }
+}
+
+impl FromStr for CellType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "integer" => Ok(CellType::Integer),
+            "decimal" => Ok(CellType::Decimal),
+            "percentage" => Ok(CellType::Percentage),
+            "date" => Ok(CellType::Date),
+            "time" => Ok(CellType::Time),
+            "datetime" => Ok(CellType::DateTime),
+            "bool" => Ok(CellType::Bool),
+            "text" => Ok(CellType::Text),
+            _ => Err(format!("unknown type: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for CellType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CellType::Integer => "integer",
+            CellType::Decimal => "decimal",
+            CellType::Percentage => "percentage",
+            CellType::Date => "date",
+            CellType::Time => "time",
+            CellType::DateTime => "datetime",
+            CellType::Bool => "bool",
+            CellType::Text => "text",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Value of a unit, parsed from its content.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellValue {
+    Integer(i64),
+    Decimal(f64),
+    /// Stored as a fraction, e.g. `0.125` for `12.5%`.
+    Percentage(f64),
+    Date(Date),
+    Time(Time),
+    DateTime(Date, Time),
+    Bool(bool),
+    Text(String),
+}
+
+impl CellValue {
+    pub fn ty(&self) -> CellType {
+        match self {
+            CellValue::Integer(_) => CellType::Integer,
+            CellValue::Decimal(_) => CellType::Decimal,
+            CellValue::Percentage(_) => CellType::Percentage,
+            CellValue::Date(_) => CellType::Date,
+            CellValue::Time(_) => CellType::Time,
+            CellValue::DateTime(..) => CellType::DateTime,
+            CellValue::Bool(_) => CellType::Bool,
+            CellValue::Text(_) => CellType::Text,
+        }
+    }
+
+    /// Gets the value as a number, if it is an integer, a decimal or a percentage.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            CellValue::Integer(n) => Some(*n as f64),
+            CellValue::Decimal(n) | CellValue::Percentage(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        self.as_number().is_some()
+    }
+
+    /// Compares two values. Numbers come first, then dates and times, booleans, and texts.
+    /// Values of the same kind compare by what they stand for.
+    pub fn compare(&self, other: &Self) -> Ordering {
+        match (self.as_number(), other.as_number()) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => match (self, other) {
+                (CellValue::Date(a), CellValue::Date(b)) => a.cmp(b),
+                (CellValue::Time(a), CellValue::Time(b)) => a.cmp(b),
+                (CellValue::DateTime(a, x), CellValue::DateTime(b, y)) => (a, x).cmp(&(b, y)),
+                (CellValue::Date(a), CellValue::DateTime(b, _)) => a.cmp(b).then(Ordering::Less),
+                (CellValue::DateTime(a, _), CellValue::Date(b)) => a.cmp(b).then(Ordering::Greater),
+                (CellValue::Bool(a), CellValue::Bool(b)) => a.cmp(b),
+                (CellValue::Text(a), CellValue::Text(b)) => a.cmp(b),
+                _ => self.rank().cmp(&other.rank()),
+            },
+        }
+    }
+
+    /// Rank of the kind of the value, in the order of [`CellValue::compare`].
+    pub(crate) fn rank(&self) -> u8 {
+        match self {
+            CellValue::Integer(_) | CellValue::Decimal(_) | CellValue::Percentage(_) => 0,
+            CellValue::Date(_) | CellValue::DateTime(..) => 1,
+            CellValue::Time(_) => 2,
+            CellValue::Bool(_) => 3,
+            CellValue::Text(_) => 4,
+        }
+    }
+}
+
+/// A date in the proleptic Gregorian calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Creates a date. Returns `None` if there is no such date.
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Self> {
+        let is_leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+        let days = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+
+        (1..=days)
+            .contains(&day)
+            .then_some(Self { year, month, day })
+    }
+}
+
+/// Parses `YYYY-MM-DD`.
+impl FromStr for Date {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '-');
+        let year = parse_digits(parts.next(), 4..=4)?;
+        let month = parse_digits(parts.next(), 1..=2)?;
+        let day = parse_digits(parts.next(), 1..=2)?;
+
+        Self::new(year as i32, month as u8, day as u8).ok_or(())
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// A time of day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Time {
+    /// Creates a time. Returns `None` if there is no such time.
+    pub fn new(hour: u8, minute: u8, second: u8) -> Option<Self> {
+        (hour < 24 && minute < 60 && second < 60).then_some(Self {
+            hour,
+            minute,
+            second,
+        })
+    }
+}
+
+/// Parses `HH:MM` or `HH:MM:SS`.
+impl FromStr for Time {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let hour = parse_digits(parts.next(), 1..=2)?;
+        let minute = parse_digits(parts.next(), 2..=2)?;
+        let second = match parts.next() {
+            Some(part) => parse_digits(Some(part), 2..=2)?,
+            None => 0,
+        };
+
+        Self::new(hour as u8, minute as u8, second as u8).ok_or(())
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+/// Parses a part of a date or time, which has a number of ASCII digits in `len`.
+fn parse_digits(part: Option<&str>, len: std::ops::RangeInclusive<usize>) -> Result<u32, ()> {
+    match part {
+        Some(s) if len.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit()) => {
+            s.parse().map_err(|_| ())
+        }
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infer() {
+        for (content, ty) in [
+            ("-12", CellType::Integer),
+            ("3.25", CellType::Decimal),
+            (".5", CellType::Decimal),
+            ("12.5 %", CellType::Percentage),
+            ("2023-04-01", CellType::Date),
+            ("9:05", CellType::Time),
+            ("2023-04-01T13:45:30", CellType::DateTime),
+            ("2023-04-01  13:45", CellType::DateTime),
+            ("TRUE", CellType::Bool),
+            ("inf", CellType::Text),
+            ("NaN", CellType::Text),
+            ("2023-02-29", CellType::Text),
+            ("24:00", CellType::Text),
+            ("12:5", CellType::Text),
+            ("", CellType::Text),
+        ] {
+            assert_eq!(CellType::infer(content), ty, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn parse_declared() {
+        assert_eq!(
+            CellType::Percentage.parse("12.5%"),
+            Some(CellValue::Percentage(0.125))
+        );
+        assert_eq!(CellType::Integer.parse("1.5"), None);
+        assert_eq!(CellType::Decimal.parse("7"), Some(CellValue::Decimal(7.0)));
+        assert_eq!(
+            CellType::Text.parse(" 7 "),
+            Some(CellValue::Text(" 7 ".to_owned()))
+        );
+        assert_eq!(
+            CellType::Date.parse("2024-02-29"),
+            Some(CellValue::Date(Date::new(2024, 2, 29).unwrap()))
+        );
+        assert_eq!(Date::new(1900, 2, 29), None);
+
+        for ty in [
+            "integer",
+            "decimal",
+            "percentage",
+            "date",
+            "time",
+            "datetime",
+            "bool",
+            "text",
+        ] {
+            assert_eq!(ty.parse::<CellType>().unwrap().to_string(), ty);
+        }
+        assert!("number".parse::<CellType>().is_err());
+    }
+
+    #[test]
+    fn compare_kinds() {
+        let values: Vec<CellValue> = [
+            "b",
+            "true",
+            "13:45",
+            "2023-04-01 10:00",
+            "2023-04-01",
+            "50%",
+            "2",
+        ]
+        .into_iter()
+        .map(|s| CellType::infer(s).parse(s).unwrap())
+        .collect();
+        let mut sorted = values.clone();
+        sorted.sort_by(CellValue::compare);
+        let types: Vec<CellType> = sorted.iter().map(CellValue::ty).collect();
+        assert_eq!(
+            types,
+            [
+                CellType::Percentage,
+                CellType::Integer,
+                CellType::Date,
+                CellType::DateTime,
+                CellType::Time,
+                CellType::Bool,
+                CellType::Text,
+            ]
+        );
+    }
+}